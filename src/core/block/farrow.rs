@@ -0,0 +1,237 @@
+use anyhow::anyhow;
+use num::{Complex, Zero};
+
+use crate::{core::{signal::SignalMeta, tag::TagBuffer}, prelude::*};
+
+/// Arbitrary-ratio resampler / fractional delay built on a cubic Lagrange Farrow interpolator.
+/// `step` is the number of input samples advanced per output sample, so a Doppler time-compression
+/// factor of 1.0007 is `step = 1.0007`. No anti-alias filtering is done, so keep `step` near 1 or
+/// low-pass the input first.
+pub struct FarrowResampler<T: SignalType> {
+    step: f64,
+    delay: f64,
+    in_rate: f64,
+    /// Input samples, `buffer[0]` is input sample `buffer_start`
+    buffer: Vec<Complex<T>>,
+    buffer_start: i64,
+    /// Input position of the next output, split into integer and fractional part
+    base: i64,
+    frac: f64,
+    next_out: i64,
+    time_offset: Option<i64>,
+    submitted: usize,
+    meta: Option<SignalMeta>,
+    tags: TagBuffer,
+}
+
+impl<T: SignalType> FarrowResampler<T> {
+    pub fn new(in_rate: f64, step: f64) -> anyhow::Result<FarrowResampler<T>> {
+        check_step(step)?;
+        Ok(FarrowResampler {
+            step,
+            delay: 0.0,
+            in_rate,
+            buffer: vec![Complex::zero()],
+            buffer_start: -1,
+            base: 0,
+            frac: 0.0,
+            next_out: 0,
+            time_offset: None,
+            submitted: 0,
+            meta: None,
+            tags: TagBuffer::new(),
+        })
+    }
+    /// Pure fractional delay, `delay` in input samples
+    pub fn delay(in_rate: f64, delay: f64) -> anyhow::Result<FarrowResampler<T>> {
+        let mut out = Self::new(in_rate, 1.0)?;
+        out.set_delay(delay);
+        Ok(out)
+    }
+    /// Takes effect on the next output sample
+    pub fn set_step(&mut self, step: f64) -> anyhow::Result<()> {
+        self.step = check_step(step)?;
+        Ok(())
+    }
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+    /// Output is `x(t - delay)`, `delay` in input samples. Takes effect on the next output sample.
+    pub fn set_delay(&mut self, delay: f64) {
+        self.delay = delay;
+    }
+    /// Nominal output rate for the current `step`
+    pub fn out_rate(&self) -> f64 {
+        self.in_rate / self.step
+    }
+    fn sample(&self, idx: i64) -> Complex<T> {
+        usize::try_from(idx - self.buffer_start).ok().and_then(|idx| self.buffer.get(idx).copied()).unwrap_or_default()
+    }
+    /// A step that is not finite and positive would never reach the end of the buffer. It fails
+    /// the whole call, which then outputs nothing and keeps its input buffered.
+    fn run(&mut self, mut step: impl FnMut(f64) -> f64) -> anyhow::Result<Signal<T>> {
+        let (base, frac, next_out) = (self.base, self.frac, self.next_out);
+        let buffer_end = self.buffer_start + self.buffer.len() as i64;
+        let mut out = Signal::new(self.out_rate());
+        out.time = self.time_offset.unwrap_or(0) + self.next_out;
+        out.meta = self.meta.clone();
+        loop {
+            let pos = self.base as f64 + self.frac - self.delay;
+            let idx = pos.floor() as i64;
+            if idx + 2 >= buffer_end {
+                break;
+            }
+            let mu = T::from_f64(pos - idx as f64).unwrap();
+            let (xm1, x0, x1, x2) = (self.sample(idx - 1), self.sample(idx), self.sample(idx + 1), self.sample(idx + 2));
+            let (two, three, six) = (T::from_f64(2.0).unwrap(), T::from_f64(3.0).unwrap(), T::from_f64(6.0).unwrap());
+            // Farrow form of the cubic Lagrange interpolator
+            let v3 = (x2 - xm1) / six + (x0 - x1) / two;
+            let v2 = (xm1 + x1) / two - x0;
+            let v1 = x1 - x0 / two - xm1 / three - x2 / six;
+            out.push(((v3 * mu + v2) * mu + v1) * mu + x0);
+
+            let step = match check_step(step(pos)) {
+                Ok(step) => step,
+                Err(err) => {
+                    (self.base, self.frac, self.next_out) = (base, frac, next_out);
+                    return Err(err);
+                }
+            };
+            self.frac += step;
+            self.base += self.frac.floor() as i64;
+            self.frac -= self.frac.floor();
+            self.next_out += 1;
+        }
+        // Keep one sample of history before the next output
+        let next_idx = (self.base as f64 + self.frac - self.delay).floor() as i64;
+        let keep_from = (next_idx - 1 - self.buffer_start).clamp(0, self.buffer.len() as i64) as usize;
+        self.buffer.drain(0..keep_from);
+        self.buffer_start += keep_from as i64;
+        Ok(out)
+    }
+    fn push(&mut self, data: &Signal<T>) {
+        if self.time_offset.is_none() {
+            self.time_offset = Some((data.time as f64 / self.step).round() as i64);
+        }
+        self.meta = data.meta.clone();
+        // Tags are re-timed with the current step and delay, exact for a fixed ratio
+        let (offset, first, delay, step) = (self.time_offset.unwrap_or(0), self.submitted as i64, self.delay, self.step);
+        self.tags.push(data, |time| offset + (((first + time - data.time) as f64 + delay) / step).round() as i64);
+        self.submitted += data.len();
+        self.buffer.extend_from_slice(data);
+    }
+    pub fn process(&mut self, data: Signal<T>) -> Signal<T> {
+        self.push(&data);
+        let step = self.step;
+        // `step` was checked when it was set
+        let mut out = self.run(|_| step).expect("valid step");
+        self.tags.attach(&mut out);
+        out
+    }
+    /// Time-varying ratio, `step` is evaluated per output sample at its input position (in samples).
+    /// Fails if it returns a step that is not finite and positive, see `new`.
+    pub fn process_varying(&mut self, data: Signal<T>, step: impl FnMut(f64) -> f64) -> anyhow::Result<Signal<T>> {
+        self.push(&data);
+        let mut out = self.run(step)?;
+        self.tags.attach(&mut out);
+        Ok(out)
+    }
+    /// Flush the interpolator. Output ends at the resampled end of the input.
+    pub fn finish(mut self) -> Signal<T> {
+        self.flush()
+    }
+    /// `finish` for callers that cannot give up ownership, the resampler must not be used afterwards
+    pub fn flush(&mut self) -> Signal<T> {
+        self.buffer.extend([Complex::zero(); 3]);
+        let step = self.step;
+        let mut out = self.run(|_| step).expect("valid step");
+        let end = self.time_offset.unwrap_or(0) + (self.submitted as f64 / self.step).ceil() as i64;
+        let len = (end - out.time).max(0) as usize;
+        out.truncate(len);
+        self.tags.attach_all(&mut out);
+        out
+    }
+    pub fn process_and_finish(mut self, data: Signal<T>) -> Signal<T> {
+        let mut out = self.process(data);
+        let mut tail = self.finish();
+        out.append(&mut tail);
+        out
+    }
+}
+
+/// Steps must be finite and positive, anything else never advances through the input
+fn check_step(step: f64) -> anyhow::Result<f64> {
+    if step > 0.0 && step.is_finite() {
+        Ok(step)
+    } else {
+        Err(anyhow!("Invalid resampling step {step}"))
+    }
+}
+
+impl<T: SignalType> Signal<T> {
+    /// Resample by an arbitrary `step` (input samples per output sample)
+    pub fn resample_arbitrary(&self, step: f64) -> anyhow::Result<Signal<T>> {
+        Ok(FarrowResampler::new(self.sample_rate, step)?.process_and_finish(self.clone()))
+    }
+    /// Delay by a (sub-)sample amount, keeping `time`, `sample_rate` and length
+    pub fn fractional_delay(&self, delay: f64) -> anyhow::Result<Signal<T>> {
+        Ok(FarrowResampler::delay(self.sample_rate, delay)?.process_and_finish(self.clone()))
+    }
+}
+
+#[test]
+fn test_farrow() -> anyhow::Result<()> {
+    use crate::core::signal::FromFunction;
+    let freq = 1000.0 * core::f64::consts::PI * 2.0;
+    let tone = |x: f64| Complex::new(f64::cos(freq * x), f64::sin(freq * x));
+    let sig = Signal::from_function(48000.0, 4800, tone);
+
+    // Quarter sample delay
+    let delayed = sig.fractional_delay(0.25)?;
+    assert_eq!((delayed.time, delayed.len()), (0, 4800));
+    let error = (10..4790).map(|idx| (delayed[idx] - tone((idx as f64 - 0.25) / 48000.0)).norm()).fold(0.0, f64::max);
+    assert!(error < 1e-3, "delay error {error}");
+
+    // Doppler compression, output sample m is the input at m * step
+    let step = 1.0007;
+    let compressed = sig.resample_arbitrary(step)?;
+    assert_eq!(compressed.len(), (4800.0 / step).ceil() as usize);
+    let error = (10..4700).map(|idx| (compressed[idx] - tone(idx as f64 * step / 48000.0)).norm()).fold(0.0, f64::max);
+    assert!(error < 1e-3, "resample error {error}");
+
+    // Streaming matches batch
+    let mut farrow = FarrowResampler::<f64>::new(48000.0, step)?;
+    let mut streamed = Signal::new(farrow.out_rate());
+    for chunk in sig.split_chunks(333) {
+        let out = farrow.process(chunk);
+        assert_eq!(out.time, streamed.end_time());
+        streamed.append(&mut out.to_vec());
+    }
+    streamed.append(&mut farrow.finish());
+    assert_eq!(streamed.len(), compressed.len());
+    assert!(streamed.iter().zip(compressed.iter()).all(|(a, b)| (a - b).norm() < 1e-12));
+
+    // Time-varying ratio follows the integrated step
+    let mut farrow = FarrowResampler::<f64>::new(48000.0, 1.0)?;
+    let varying = farrow.process_varying(sig.clone(), |pos| 1.0 + 1e-4 * pos / 4800.0)?;
+    let mut pos = 0.0;
+    for sample in varying.iter().take(4000) {
+        assert!((sample - tone(pos / 48000.0)).norm() < 1e-3);
+        pos += 1.0 + 1e-4 * pos / 4800.0;
+    }
+
+    // Steps that would never get through the input are rejected instead of looping
+    assert!(FarrowResampler::<f64>::new(48000.0, 0.0).is_err());
+    let mut farrow = FarrowResampler::<f64>::new(48000.0, 1.0)?;
+    assert!(farrow.set_step(-1.0).is_err() && farrow.set_step(f64::INFINITY).is_err());
+    assert_eq!(farrow.step(), 1.0);
+    let chunk = sig.split_chunks(480)[0].clone();
+    for bad in [0.0, -0.5, f64::NAN] {
+        assert!(farrow.process_varying(chunk.clone(), |pos| if pos < 100.0 { 1.0 } else { bad }).is_err());
+    }
+    // Nothing was output, a good step picks up from the first sample
+    let resumed = farrow.process_varying(Signal::new(48000.0), |_| 1.0)?;
+    assert_eq!((resumed.time, resumed.len()), (0, 3 * 480 - 2));
+    assert!((resumed[10] - tone(10.0 / 48000.0)).norm() < 1e-3);
+    Ok(())
+}
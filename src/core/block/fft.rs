@@ -55,6 +55,85 @@ impl<T: SignalType> FftInst<T> for RustFftInst<T> {
     }
 }
 
+/// FFT of real data of even length `len` through one complex FFT of `len/2`: even samples go in
+/// the real part, odd samples in the imaginary part, and the two halves are split apart after.
+/// Only the `len/2 + 1` non-negative frequency bins are stored. Scaled like `RustFftInst`.
+pub struct RealFftInst<T: SignalType> {
+    len: usize,
+    scale_factor: T,
+    /// `e^(-j*2*pi*k/len)` for `k` in `0..=len/2`
+    twiddles: Vec<Complex<T>>,
+    buffer: Mutex<Vec<Complex<T>>>,
+    half: RustFftInst<T>,
+}
+impl<T: SignalType> RealFftInst<T> {
+    /// `len` must be even and not 0
+    pub fn new(len: usize) -> anyhow::Result<RealFftInst<T>> {
+        if len == 0 || len % 2 == 1 {
+            return Err(anyhow::anyhow!("Real FFT length must be even and non-zero, got {len}"));
+        }
+        let half = len / 2;
+        let twiddles = (0..=half).map(|k| {
+            let angle = -2.0 * std::f64::consts::PI * k as f64 / len as f64;
+            Complex::new(T::from_f64(angle.cos()).unwrap(), T::from_f64(angle.sin()).unwrap())
+        }).collect_vec();
+        Ok(RealFftInst {
+            len,
+            // `half` is already scaled by `1/sqrt(len/2)`
+            scale_factor: T::one() / T::sqrt(T::from_f64(2.0).unwrap()),
+            twiddles,
+            buffer: Mutex::new(vec![Complex::zero(); half]),
+            half: RustFftInst::new(half),
+        })
+    }
+    /// Number of bins `fft_fwd` produces, `len/2 + 1`
+    pub fn bins(&self) -> usize {
+        self.len / 2 + 1
+    }
+    /// `input.len()` must be `len`, `output.len()` must be `bins()`
+    pub fn fft_fwd(&self, input: &[T], output: &mut [Complex<T>]) -> anyhow::Result<()> {
+        if input.len() != self.len || output.len() != self.bins() {
+            return Err(anyhow::anyhow!("Real FFT of {} expects {} samples and {} bins, got {} and {}", self.len, self.len, self.bins(), input.len(), output.len()));
+        }
+        let half = self.len / 2;
+        let mut packed = self.buffer.lock().unwrap();
+        packed.iter_mut().zip(input.chunks_exact(2)).for_each(|(z, x)| *z = Complex::new(x[0], x[1]));
+        self.half.fft_fwd(&mut packed)?;
+        let one_half = T::from_f64(0.5).unwrap();
+        output.iter_mut().enumerate().for_each(|(k, x)| {
+            let z = packed[k % half];
+            let zc = packed[(half - k % half) % half].conj();
+            // Spectra of the even and odd samples
+            let even = (z + zc) * one_half;
+            let odd = (z - zc) * Complex::new(T::zero(), -one_half);
+            *x = (even + self.twiddles[k] * odd) * self.scale_factor;
+        });
+        Ok(())
+    }
+    /// Inverse of `fft_fwd`, the imaginary parts of the DC and Nyquist bins are ignored
+    pub fn fft_rev(&self, input: &[Complex<T>], output: &mut [T]) -> anyhow::Result<()> {
+        if input.len() != self.bins() || output.len() != self.len {
+            return Err(anyhow::anyhow!("Inverse real FFT of {} expects {} bins and {} samples, got {} and {}", self.len, self.bins(), self.len, input.len(), output.len()));
+        }
+        let half = self.len / 2;
+        let mut packed = self.buffer.lock().unwrap();
+        let one_half = T::from_f64(0.5).unwrap();
+        packed.iter_mut().enumerate().for_each(|(k, z)| {
+            let x = input[k];
+            let xc = input[half - k].conj();
+            let even = (x + xc) * one_half;
+            let odd = (x - xc) * self.twiddles[k].conj() * one_half;
+            *z = (even + Complex::new(T::zero(), T::one()) * odd) / self.scale_factor;
+        });
+        self.half.fft_rev(&mut packed)?;
+        output.chunks_exact_mut(2).zip(packed.iter()).for_each(|(x, z)| {
+            x[0] = z.re;
+            x[1] = z.im;
+        });
+        Ok(())
+    }
+}
+
 impl<T: SignalType> Signal<T> {
     pub fn fft_fwd(mut self) -> anyhow::Result<Signal<T>> {
         RustFftInst::new(self.len()).fft_fwd(&mut self)?;
@@ -85,5 +164,17 @@ fn test_fft() -> anyhow::Result<()> {
     trace!("avg error: {}, max: {}", (error.iter().cloned().sum::<f32>())/(error.len()as f32), error.iter().max_by(|a,b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Less)).unwrap_or(&0.0));
 
 
+    // Real FFT: same bins as the complex FFT of the same samples, and back
+    let samples = (0..64).map(|x| f64::sin(x as f64 * 0.7) + 0.1 * x as f64).collect_vec();
+    let reference = Signal::from_vec(1000.0, samples.clone()).fft_fwd()?;
+    let fft = RealFftInst::<f64>::new(64)?;
+    let mut bins = vec![Complex::zero(); fft.bins()];
+    fft.fft_fwd(&samples, &mut bins)?;
+    assert!(bins.iter().zip(reference.iter()).all(|(a, b)| (a - b).norm() < 1e-12));
+    let mut restored = vec![0.0; 64];
+    fft.fft_rev(&bins, &mut restored)?;
+    assert!(restored.iter().zip(samples.iter()).all(|(a, b)| (a - b).abs() < 1e-12));
+    assert!(fft.fft_fwd(&samples[1..], &mut bins).is_err());
+    assert!(RealFftInst::<f64>::new(0).is_err() && RealFftInst::<f64>::new(63).is_err());
     Ok(())
 }
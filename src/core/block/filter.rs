@@ -1,15 +1,25 @@
 use anyhow::{anyhow, Context};
+use itertools::Itertools;
+use log::warn;
 use log::info;
 use num::{bigint::Sign, Complex, Zero};
 use rkyv::api::high;
 
-use crate::{core::{block::{fft::{FftInst, RustFftInst}, refragment::Refragmenter}, r#gen::{chirp::chirp_complex, fir::{fir_bpf, fir_hpf, fir_lpf}}}, plot::spectrum::spectrogram, prelude::*};
+use crate::{core::{block::{fft::{FftInst, RealFftInst, RustFftInst}, refragment::Refragmenter}, multi_signal::MultiSignal, real_signal::RealSignal, signal::SignalMeta, stream::Discontinuity, tag::TagBuffer, r#gen::{chirp::chirp_complex, fir::{fir_bpf, fir_hpf, fir_lpf}}}, plot::spectrum::spectrogram, prelude::*};
+
+/// FFT length `Filter` uses for a kernel of `kern_len` taps. `fft_fwd`/`fft_rev` are unitary,
+/// so the filter output is scaled by `1/sqrt` of it.
+pub(crate) fn fft_len(kern_len: usize) -> usize {
+    8 * 2_usize.pow((kern_len as u32 - 1).ilog2()+1)
+}
 
 // Overlap-add: https://en.wikipedia.org/wiki/Overlap%E2%80%93add_method
 pub struct Filter<T: SignalType, FFT: FftInst<T> = RustFftInst<T>> {
     refrag: Refragmenter<T>,
     kernel_fft: Signal<T>,
     fft: FFT,
+    /// Half-length FFT and half spectrum of the kernel, only for filters built by `new_real`
+    real: Option<(RealFftInst<T>, Vec<Complex<T>>)>,
     len: usize,
     time_delay: usize,
     submitted: usize,
@@ -17,19 +27,25 @@ pub struct Filter<T: SignalType, FFT: FftInst<T> = RustFftInst<T>> {
     buffer: Signal<T>,
     overlap: Vec<Complex<T>>,
     sample_rate: f64,
+    meta: Option<SignalMeta>,
+    tags: TagBuffer,
 }
 impl<T: SignalType, FFT: FftInst<T>> Filter<T, FFT> {
-    pub fn new(mut kernel: Signal<T>) -> anyhow::Result<Filter<T, FFT>> {
+    pub fn new(kernel: impl Into<Signal<T>>) -> anyhow::Result<Filter<T, FFT>> {
+        let kernel: Signal<T> = kernel.into();
         let kern_len = kernel.len();
         let sample_rate = kernel.sample_rate;
-        let len = 8 * 2_usize.pow((kern_len as u32 - 1).ilog2()+1);
+        let len = fft_len(kern_len);
         let mut fft = FFT::new(len);
 
         let step_size = len-(kern_len-1);
 
-        let refrag = Refragmenter::<T>::new(kernel.sample_rate, step_size);
+        let refrag = Refragmenter::<T>::new(kernel.sample_rate, step_size).with_max_gap(len);
 
         let mut kernel = kernel.clone();
+        // Only the taps matter, whatever time the kernel was cut from
+        kernel.time = 0;
+        kernel.tags.clear();
         kernel.append(&mut vec![Complex::zero(); len-kern_len]);
         fft.fft_fwd(&mut kernel)?;
 
@@ -37,53 +53,121 @@ impl<T: SignalType, FFT: FftInst<T>> Filter<T, FFT> {
             refrag,
             kernel_fft: kernel,
             fft,
+            real: None,
             len,
             submitted: 0,
             step_size,
             buffer: Signal::from_vec(sample_rate, vec![Complex::zero(); len]),
             overlap: vec![Complex::zero(); len-step_size],
             sample_rate,
+            meta: None,
+            tags: TagBuffer::new(),
             time_delay: (len-step_size)/2
         })
     }
-    fn process_chunk(&mut self, chunk: &[Complex<T>]) -> anyhow::Result<Signal<T>> {
+    /// Filter for real signals with a real kernel. `process_real` and `finish_real` then use
+    /// half-length FFTs, `process` still takes complex input through the full-length FFT.
+    pub fn new_real(kernel: &RealSignal<T>) -> anyhow::Result<Filter<T, FFT>> {
+        let mut filter = Self::new(kernel.to_complex())?;
+        let fft = RealFftInst::new(filter.len)?;
+        let mut taps = kernel.to_vec();
+        taps.resize(filter.len, T::zero());
+        let mut spectrum = vec![Complex::zero(); fft.bins()];
+        fft.fft_fwd(&taps, &mut spectrum)?;
+        filter.real = Some((fft, spectrum));
+        Ok(filter)
+    }
+    fn process_chunk(&mut self, chunk: &[Complex<T>], real: bool) -> anyhow::Result<Signal<T>> {
+        if real && self.real.is_some() {
+            return self.process_real_chunk(chunk);
+        }
         self.buffer[0..self.step_size].clone_from_slice(chunk);
         self.buffer[self.step_size..].fill(Complex::zero());
         self.fft.fft_fwd(&mut self.buffer)?;
-        let mut out = &self.buffer * &self.kernel_fft;
+        // Bin by bin, the spectra have no time axis to align
+        let mut out = self.buffer.clone();
+        out.iter_mut().zip(self.kernel_fft.iter()).for_each(|(a, b)| *a *= *b);
         self.fft.fft_rev(&mut out)?;
         self.overlap.iter().enumerate().for_each(|(idx, v)| out[idx] += *v);
         self.overlap = out.split_off(self.step_size);
         Ok(out)
     }
-    pub fn process(&mut self, mut data: Signal<T>) -> Option<Signal<T>> {
+    /// `process_chunk` on the real parts only, through half-length FFTs. The kernel is real, so
+    /// the overlap carried to the next chunk is real too and either path can continue from it.
+    fn process_real_chunk(&mut self, chunk: &[Complex<T>]) -> anyhow::Result<Signal<T>> {
+        let (fft, kernel) = self.real.as_ref().context("not a real filter")?;
+        let mut samples = chunk.iter().map(|x| x.re).collect_vec();
+        samples.resize(self.len, T::zero());
+        let mut spectrum = vec![Complex::zero(); fft.bins()];
+        fft.fft_fwd(&samples, &mut spectrum)?;
+        spectrum.iter_mut().zip(kernel.iter()).for_each(|(a, b)| *a *= *b);
+        fft.fft_rev(&spectrum, &mut samples)?;
+        let mut out = Signal::from_vec(self.sample_rate, samples);
+        self.overlap.iter().enumerate().for_each(|(idx, v)| out[idx].re += v.re);
+        self.overlap = out.split_off(self.step_size);
+        Ok(out)
+    }
+    /// Gaps marked on `data` are zero-filled up to the FFT length, a `Reset` restarts the
+    /// overlap-add from silence. A longer gap is a `Reset` whose output continues after the gap.
+    pub fn process(&mut self, data: Signal<T>) -> Option<Signal<T>> {
+        self.process_with(data, false)
+    }
+    fn process_with(&mut self, mut data: Signal<T>, real: bool) -> Option<Signal<T>> {
+        match data.stream.and_then(|info| info.discontinuity) {
+            Some(Discontinuity::Gap { samples }) if samples.max(0) as usize <= self.len => self.submitted += samples.max(0) as usize,
+            Some(Discontinuity::Gap { samples }) => {
+                self.overlap.fill(Complex::zero());
+                self.tags.discard_from((self.submitted - self.refrag.pending()) as i64);
+                self.submitted += samples as usize;
+            }
+            Some(Discontinuity::Reset) => {
+                self.submitted -= self.refrag.pending();
+                self.overlap.fill(Complex::zero());
+                self.tags.discard_from(self.submitted as i64);
+            }
+            None => {}
+        }
+        // Output is delay compensated, so a tag keeps its sample index on the filter's time axis
+        let first = self.submitted as i64;
+        self.tags.push(&data, |time| first + time - data.time);
         self.submitted += data.len();
+        self.meta = data.meta.clone();
         self.refrag.push(&mut data);
 
         // First fragment determines start time of `out`
         let mut output = Signal::new(self.kernel_fft.sample_rate);
+        output.meta = self.meta.clone();
         let Some(mut frag) = (&mut self.refrag).next() else {
             return None;
         };
         
         output.time = frag.time - self.time_delay as i64;
-        let Ok(mut frag) = self.process_chunk(&mut frag) else {return None};
+        let Ok(mut frag) = self.process_chunk(&mut frag, real) else {return None};
         output.append(&mut frag);
         
         loop {
             let Some(mut frag) = (&mut self.refrag).next() else {break;};
-            let Ok(mut frag) = self.process_chunk(&mut frag) else {break;};
+            let Ok(mut frag) = self.process_chunk(&mut frag, real) else {break;};
             output.append(&mut frag);
         }
 
         //trace!("out_time: {}", output.time);
+        self.tags.attach(&mut output);
         
         Some(output)
     }
     pub fn finish(mut self) -> Option<Signal<T>> {
+        self.flush()
+    }
+    /// `finish` for callers that cannot give up ownership, the filter must not be used afterwards
+    pub fn flush(&mut self) -> Option<Signal<T>> {
+        self.flush_with(false)
+    }
+    fn flush_with(&mut self, real: bool) -> Option<Signal<T>> {
         //let mut output = Signal::<T>::new(self.kernel_fft.sample_rate);
         self.refrag.push(&mut Signal::from_vec(self.kernel_fft.sample_rate, vec![Complex::zero(); self.step_size]));
         let mut output = Signal::new(self.kernel_fft.sample_rate);
+        output.meta = self.meta.clone();
 
         // First fragment determines start time of `out`
         let Some(mut frag) = (&mut self.refrag).next() else {
@@ -91,12 +175,12 @@ impl<T: SignalType, FFT: FftInst<T>> Filter<T, FFT> {
         };
         
         output.time = frag.time - self.time_delay as i64;
-        let Ok(mut frag) = self.process_chunk(&mut frag) else {return None};
+        let Ok(mut frag) = self.process_chunk(&mut frag, real) else {return None};
         output.append(&mut frag);
 
         loop {
             let Some(mut frag) = (&mut self.refrag).next() else {break;};
-            let Ok(mut frag) = self.process_chunk(&mut frag) else {break;};
+            let Ok(mut frag) = self.process_chunk(&mut frag, real) else {break;};
             output.append(&mut frag);
         }
 
@@ -105,6 +189,7 @@ impl<T: SignalType, FFT: FftInst<T>> Filter<T, FFT> {
         trace!("finish_time : trunc_output_len <==> {finish_time} : {trunc_output_len}");
 
         output.truncate(trunc_output_len);
+        self.tags.attach_all(&mut output);
         
         Some(output)
     }
@@ -116,6 +201,21 @@ impl<T: SignalType, FFT: FftInst<T>> Filter<T, FFT> {
         }
         Some(filtered)
     }
+    /// Real in, real out: the real part of the output. Half-length FFTs for a filter built by
+    /// `new_real`, otherwise the same work as `process`.
+    pub fn process_real(&mut self, data: RealSignal<T>) -> Option<RealSignal<T>> {
+        self.process_with(data.into(), true).map(|x| x.to_real())
+    }
+    pub fn finish_real(mut self) -> Option<RealSignal<T>> {
+        self.flush_with(true).map(|x| x.to_real())
+    }
+    pub fn process_and_finish_real(mut self, data: RealSignal<T>) -> Option<RealSignal<T>> {
+        let mut filtered = self.process_with(data.into(), true)?;
+        if let Some(mut sig) = self.flush_with(true) {
+            filtered.append(&mut sig);
+        }
+        Some(filtered.to_real())
+    }
     /// cutoff is real frequency
     pub fn lowpass(cutoff: f64,  order: usize, sample_rate: f64) -> anyhow::Result<Filter<T, FFT>> {
         let Some(kern) = fir_lpf::<T>(cutoff/sample_rate*2.0, order) else {
@@ -145,21 +245,61 @@ impl<T: SignalType, FFT: FftInst<T>> Filter<T, FFT> {
     }
 }
 
+/// One `Filter` per channel, all sharing the same kernel
+pub struct MultiFilter<T: SignalType, FFT: FftInst<T> = RustFftInst<T>> {
+    filters: Vec<Filter<T, FFT>>,
+}
+impl<T: SignalType, FFT: FftInst<T>> MultiFilter<T, FFT> {
+    pub fn new(kernel: impl Into<Signal<T>>, n_channels: usize) -> anyhow::Result<MultiFilter<T, FFT>> {
+        let kernel: Signal<T> = kernel.into();
+        let filters = (0..n_channels).map(|_| Filter::new(kernel.clone())).try_collect()?;
+        Ok(MultiFilter { filters })
+    }
+    pub fn process(&mut self, data: MultiSignal<T>) -> Option<MultiSignal<T>> {
+        if data.n_channels() != self.filters.len() {
+            warn!("MultiFilter expected {} channels, got {}", self.filters.len(), data.n_channels());
+            return None;
+        }
+        let channels: Option<Vec<_>> = self.filters.iter_mut().zip(data.into_channels()).map(|(filt, ch)| filt.process(ch)).collect();
+        MultiSignal::from_channels(channels?).ok()
+    }
+    pub fn finish(self) -> Option<MultiSignal<T>> {
+        let channels: Option<Vec<_>> = self.filters.into_iter().map(|filt| filt.finish()).collect();
+        MultiSignal::from_channels(channels?).ok()
+    }
+    pub fn process_and_finish(mut self, data: MultiSignal<T>) -> Option<MultiSignal<T>> {
+        let mut filtered = self.process(data)?;
+        if let Some(mut sig) = self.finish() {
+            filtered.append(&mut sig).ok()?;
+        }
+        Some(filtered)
+    }
+}
+
 pub enum ConvShape {
     FULL,
     SAME,
     VALID
 }
-/// Convenience API
+/// Convenience API. The output is placed on the time axis of `signal`.
 pub fn fftfilt<T: SignalType>(signal: &Signal<T>, filter: &Signal<T>, shape: ConvShape) -> anyhow::Result<Signal<T>> {
-    let in_len = signal.len();
-    let sample_rate = signal.sample_rate;
     let filt = Filter::<T>::new(filter.clone())?;
     let out = filt.process_and_finish(signal.clone()).context("filter did not return values")?;
+    Ok(shaped(out, signal.time, signal.len(), shape))
+}
+/// `fftfilt` of real signals through the real FFT path, see `Filter::process_real`
+pub fn fftfilt_real<T: SignalType>(signal: &RealSignal<T>, filter: &RealSignal<T>, shape: ConvShape) -> anyhow::Result<RealSignal<T>> {
+    let filt = Filter::<T>::new_real(filter)?;
+    let out = filt.process_and_finish_real(signal.clone()).context("filter did not return values")?;
+    Ok(shaped(out.to_complex(), signal.time, signal.len(), shape).to_real())
+}
+fn shaped<T: SignalType>(mut out: Signal<T>, time: i64, in_len: usize, shape: ConvShape) -> Signal<T> {
+    let delay = out.time.unsigned_abs() as usize;
+    out.time += time;
     match shape {
-        ConvShape::FULL => Ok(out),
-        ConvShape::SAME => Ok(Signal::from_vec(sample_rate, out[out.time.abs() as usize .. out.time.abs() as usize + in_len].to_vec())),
-        ConvShape::VALID => Ok(Signal::from_vec(sample_rate, out[out.time.abs() as usize*2  .. in_len].to_vec())),
+        ConvShape::FULL => out,
+        ConvShape::SAME => out.slice(delay .. delay + in_len),
+        ConvShape::VALID => out.slice(delay*2 .. in_len),
     }
 }
 
@@ -203,5 +343,40 @@ fn test_filter() -> anyhow::Result<()> {
     trace!("len_valid: {}", filtered.len());
 
     spectrogram("plot/test/test_filter/fftfilt_valid_bpf_spect.png", filtered, 512, 512-128, true, Some(-120.0));
+    // A kernel cut out of a longer signal filters exactly like the same taps at time 0
+    let taps = fir_lpf::<f64>(0.25, 32).unwrap();
+    let kernel = Signal::from_vec(1000.0, taps);
+    let mut shifted = kernel.clone();
+    shifted.time = 100;
+    let input = Signal::from_vec(1000.0, (0..500).map(|x| Complex::new((x as f64 * 0.3).sin(), 0.0)).collect());
+    let expected = Filter::<f64>::new(kernel)?.process_and_finish(input.clone()).unwrap();
+    let filtered = Filter::<f64>::new(shifted)?.process_and_finish(input).unwrap();
+    assert_eq!((filtered.time, filtered.len()), (expected.time, expected.len()));
+    assert!(filtered.iter().zip(expected.iter()).all(|(a, b)| (a - b).norm() < 1e-12));
+
+    // The real path matches the real part of the complex path, across chunks and on flush
+    let taps = RealSignal::from_vec(1000.0, fir_bpf::<f64>(0.1, 0.4, 48).unwrap());
+    let input = RealSignal::from_vec(1000.0, (0..1500).map(|x| (x as f64 * 0.37).sin() + 0.2 * (x as f64 * 1.9).cos()).collect());
+    let expected = Filter::<f64>::new(taps.to_complex())?.process_and_finish(input.to_complex()).unwrap();
+    let mut filter = Filter::<f64>::new_real(&taps)?;
+    let mut out = filter.process_real(RealSignal::from_vec(1000.0, input[..700].to_vec())).unwrap();
+    let mut rest = RealSignal::from_vec(1000.0, input[700..].to_vec());
+    rest.time = 700;
+    out.extend(filter.process_real(rest).unwrap().iter());
+    out.extend(filter.finish_real().unwrap().iter());
+    assert_eq!((out.time, out.len()), (expected.time, expected.len()));
+    assert!(out.iter().zip(expected.iter()).all(|(a, b)| (a - b.re).abs() < 1e-12));
+
+    // A complex kernel keeps its imaginary part in `process` after `process_real`
+    let complex = Signal::from_vec(1000.0, taps.iter().enumerate().map(|(idx, x)| Complex::new(*x, *x * idx as f64 / 48.0)).collect());
+    let expected = Filter::<f64>::new(complex.clone())?.process_and_finish(input.to_complex()).unwrap();
+    let mut filter = Filter::<f64>::new(complex)?;
+    let first = filter.process_real(RealSignal::from_vec(1000.0, input[..700].to_vec())).unwrap();
+    let mut rest = input.to_complex().slice(700..1500);
+    rest.time = 700;
+    let mut second = filter.process(rest).unwrap();
+    second.append(&mut filter.finish().unwrap());
+    assert!(first.iter().zip(expected.iter()).all(|(a, b)| (a - b.re).abs() < 1e-12));
+    assert!(second.iter().zip(expected[first.len()..].iter()).all(|(a, b)| (a - b).norm() < 1e-12));
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,153 @@
+use anyhow::anyhow;
+use itertools::Itertools;
+use num::{Complex, Zero};
+
+use crate::{core::{fixed::{round_shift, FixedSignal, FixedStorage, Rounding, Q}, signal::SignalMeta}};
+
+/// Direct-form fixed-point FIR, sample for sample what an integer DSP loop computes:
+/// products of `FRAC` data and `COEF_FRAC` taps are summed in `S::Acc` without overflow, then
+/// shifted back by `COEF_FRAC` and saturated. Real taps, applied to I and Q separately.
+/// Output `time` follows `Filter`, i.e. it is shifted back by the group delay of a symmetric kernel.
+pub struct FixedFir<S: FixedStorage, const FRAC: u32, const COEF_FRAC: u32> {
+    taps: Vec<Q<S, COEF_FRAC>>,
+    /// Last `taps.len() - 1` inputs, oldest first
+    history: Vec<Complex<Q<S, FRAC>>>,
+    rounding: Rounding,
+    sample_rate: f64,
+    time_offset: Option<i64>,
+    submitted: usize,
+    saturated: usize,
+    meta: Option<SignalMeta>,
+}
+
+impl<S: FixedStorage, const FRAC: u32, const COEF_FRAC: u32> FixedFir<S, FRAC, COEF_FRAC> {
+    pub fn new(sample_rate: f64, taps: Vec<Q<S, COEF_FRAC>>) -> anyhow::Result<Self> {
+        if taps.is_empty() {
+            return Err(anyhow!("FIR needs at least one tap"));
+        }
+        Ok(FixedFir {
+            history: vec![Complex::new(Q::default(), Q::default()); taps.len() - 1],
+            taps,
+            rounding: Rounding::Truncate,
+            sample_rate,
+            time_offset: None,
+            submitted: 0,
+            saturated: 0,
+            meta: None,
+        })
+    }
+    /// Quantize float taps, fails if any tap does not fit in `Q<S, COEF_FRAC>`
+    pub fn from_float(sample_rate: f64, taps: &[f64]) -> anyhow::Result<Self> {
+        let taps = taps.iter().map(|tap| Q::checked_from_f64(*tap).ok_or(anyhow!("Tap {tap} does not fit in Q{COEF_FRAC}"))).try_collect()?;
+        Self::new(sample_rate, taps)
+    }
+    pub fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+    pub fn taps(&self) -> &[Q<S, COEF_FRAC>] {
+        &self.taps
+    }
+    /// Group delay of a symmetric kernel in samples
+    pub fn delay(&self) -> usize {
+        (self.taps.len() - 1) / 2
+    }
+    /// Number of output components clipped so far
+    pub fn saturated(&self) -> usize {
+        self.saturated
+    }
+    fn run(&mut self, input: &[Complex<Q<S, FRAC>>]) -> Vec<Complex<Q<S, FRAC>>> {
+        let mut window = std::mem::take(&mut self.history);
+        window.extend_from_slice(input);
+        let n_taps = self.taps.len();
+        let out = window.windows(n_taps).map(|w| {
+            let (mut re, mut im) = (S::Acc::zero(), S::Acc::zero());
+            // Newest sample meets taps[0]
+            for (tap, x) in self.taps.iter().zip(w.iter().rev()) {
+                re = re + tap.raw().widen() * x.re.raw().widen();
+                im = im + tap.raw().widen() * x.im.raw().widen();
+            }
+            let (re, im) = (round_shift::<S>(re, COEF_FRAC, self.rounding), round_shift::<S>(im, COEF_FRAC, self.rounding));
+            let (re_out, im_out) = (S::saturate(re), S::saturate(im));
+            self.saturated += usize::from(re_out.widen() != re) + usize::from(im_out.widen() != im);
+            Complex::new(Q::from_raw(re_out), Q::from_raw(im_out))
+        }).collect_vec();
+        self.history = window.split_off(window.len() - (n_taps - 1));
+        out
+    }
+    fn output(&self, samples: Vec<Complex<Q<S, FRAC>>>, first: usize) -> FixedSignal<S, FRAC> {
+        let mut out = FixedSignal::from_vec(self.sample_rate, samples);
+        out.time = self.time_offset.unwrap_or(0) + first as i64 - self.delay() as i64;
+        out.meta = self.meta.clone();
+        out
+    }
+    pub fn process(&mut self, data: FixedSignal<S, FRAC>) -> FixedSignal<S, FRAC> {
+        self.time_offset.get_or_insert(data.time);
+        self.meta = data.meta.clone();
+        let first = self.submitted;
+        self.submitted += data.len();
+        let samples = self.run(&data);
+        self.output(samples, first)
+    }
+    /// Flush the `taps.len() - 1` sample tail
+    pub fn finish(mut self) -> FixedSignal<S, FRAC> {
+        let zeros = vec![Complex::new(Q::default(), Q::default()); self.taps.len() - 1];
+        let first = self.submitted;
+        let samples = self.run(&zeros);
+        self.output(samples, first)
+    }
+    pub fn process_and_finish(mut self, data: FixedSignal<S, FRAC>) -> FixedSignal<S, FRAC> {
+        let mut out = self.process(data);
+        out.append(&mut self.finish());
+        out
+    }
+}
+
+#[test]
+fn test_fixed_filter() -> anyhow::Result<()> {
+    use std::f64::consts::PI;
+    use crate::{core::{block::filter::{fft_len, Filter}, r#gen::fir::fir_lpf, signal::FromFunction}, prelude::*};
+
+    // Unit DC gain low-pass
+    let taps = fir_lpf::<f64>(0.25, 32).unwrap();
+    let gain: f64 = taps.iter().sum();
+    let taps = taps.iter().map(|x| x / gain).collect_vec();
+
+    let mut sig = Signal::from_function(8000.0, 1000, |x| Complex::new(0.4 * f64::cos(300.0 * 2.0 * PI * x), 0.4 * f64::sin(300.0 * 2.0 * PI * x)));
+    sig.time = 100;
+    let fixed = FixedSignal::<i16, 15>::from_signal(&sig);
+
+    // Streaming in chunks is bit-identical to one block
+    let batch = FixedFir::<i16, 15, 15>::from_float(8000.0, &taps)?.process_and_finish(fixed.clone());
+    let mut fir = FixedFir::<i16, 15, 15>::from_float(8000.0, &taps)?;
+    let mut streamed = FixedSignal::<i16, 15>::new(8000.0);
+    for chunk in fixed.chunks(77) {
+        let mut chunk = FixedSignal::from_vec(8000.0, chunk.to_vec());
+        chunk.time = fixed.time + streamed.len() as i64;
+        let mut out = fir.process(chunk);
+        if streamed.is_empty() {
+            streamed.time = out.time;
+        }
+        streamed.append(&mut out);
+    }
+    assert_eq!(fir.saturated(), 0);
+    streamed.append(&mut fir.finish());
+    assert_eq!((streamed.time, streamed.len()), (batch.time, batch.len()));
+    assert_eq!(streamed.to_vec(), batch.to_vec());
+
+    // Matches the float filter to within a few LSB, once its `1/sqrt(fft_len)` is undone
+    let float = Filter::<f64>::new(Signal::from_vec(8000.0, taps.clone()))?.process_and_finish(sig.clone()).unwrap();
+    assert_eq!((batch.time, batch.len()), (float.time + sig.time, float.len()));
+    let scale = (fft_len(taps.len()) as f64).sqrt();
+    let error = batch.to_signal::<f64>().iter().zip(float.iter()).map(|(a, b)| (a - b * scale).norm()).fold(0.0, f64::max);
+    assert!(error < 8.0 * Q::<i16, 15>::resolution(), "fixed-point error {error}");
+
+    // Overdriven input saturates instead of wrapping
+    let loud = FixedSignal::<i16, 15>::from_vec(8000.0, vec![Complex::new(Q::from_f64(0.99), Q::from_f64(-0.99)); 100]);
+    let mut fir = FixedFir::<i16, 15, 14>::from_float(8000.0, &[1.5, 0.5])?.with_rounding(Rounding::Nearest);
+    let out = fir.process(loud);
+    assert!(out[1..].iter().all(|x| x.re == Q::max_value() && x.im == Q::min_value()));
+    assert_eq!(fir.saturated(), 2 * 100);
+    assert!(FixedFir::<i16, 15, 15>::from_float(8000.0, &[1.5]).is_err());
+    Ok(())
+}
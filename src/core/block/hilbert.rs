@@ -0,0 +1,166 @@
+use std::f64::consts::PI;
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use num::{Complex, Zero};
+
+use crate::{core::block::{fft::{FftInst, RealFftInst, RustFftInst}, filter::{fft_len, Filter}}, prelude::*};
+
+/// Odd length FIR Hilbert transformer (Hamming windowed), symmetric around `order/2`
+pub fn fir_hilbert<T: SignalType>(order: usize) -> Option<Vec<T>> {
+    let len = order / 2 * 2 + 1;
+    let center = (len / 2) as i64;
+    (0..len as i64).map(|n| {
+        let k = n - center;
+        let ideal = if k % 2 == 0 { 0.0 } else { 2.0 / (PI * k as f64) };
+        let window = 0.54 - 0.46 * f64::cos(2.0 * PI * n as f64 / (len - 1) as f64);
+        T::from_f64(ideal * window)
+    }).collect()
+}
+
+/// Streaming analytic-signal generator: `x + j*hilbert(x)` through a single complex `Filter`.
+/// The imaginary part of the input is ignored. `time` is compensated for the filter delay.
+pub struct HilbertFilter<T: SignalType> {
+    filter: Filter<T>,
+}
+impl<T: SignalType> HilbertFilter<T> {
+    /// Longer filters extend the usable band towards 0 Hz and Nyquist
+    pub fn new(sample_rate: f64, order: usize) -> anyhow::Result<HilbertFilter<T>> {
+        let hilbert = fir_hilbert::<T>(order).ok_or(anyhow!("Failed to construct Hilbert transformer"))?;
+        let center = hilbert.len() / 2;
+        // Unit gain, undoing the `1/sqrt(fft_len)` of the filter
+        let scale = T::from_usize(fft_len(hilbert.len())).unwrap().sqrt();
+        let kernel = hilbert.iter().enumerate().map(|(idx, h)| {
+            Complex::new(if idx == center { T::one() } else { T::zero() }, *h) * scale
+        }).collect_vec();
+        Ok(HilbertFilter { filter: Filter::new(Signal::from_vec(sample_rate, kernel))? })
+    }
+    pub fn process(&mut self, data: Signal<T>) -> Option<Signal<T>> {
+        self.filter.process(real_part(data))
+    }
+    pub fn finish(self) -> Option<Signal<T>> {
+        self.filter.finish()
+    }
+    pub fn flush(&mut self) -> Option<Signal<T>> {
+        self.filter.flush()
+    }
+    pub fn process_and_finish(self, data: Signal<T>) -> Option<Signal<T>> {
+        self.filter.process_and_finish(real_part(data))
+    }
+}
+
+fn real_part<T: SignalType>(mut data: Signal<T>) -> Signal<T> {
+    data.iter_mut().for_each(|x| x.im = T::zero());
+    data
+}
+
+impl<T: SignalType> Signal<T> {
+    /// Analytic signal of the real part via FFT (negative frequencies removed)
+    pub fn analytic(&self) -> anyhow::Result<Signal<T>> {
+        let len = self.len();
+        let mut out = real_part(self.clone());
+        let fft = RustFftInst::<T>::new(len);
+        fft.fft_fwd(&mut out)?;
+        let two = T::from_f64(2.0).unwrap();
+        out.iter_mut().enumerate().for_each(|(idx, x)| {
+            // DC and (even length) Nyquist are kept as-is
+            if idx != 0 && 2 * idx < len {
+                *x *= two;
+            } else if 2 * idx > len {
+                *x = Complex::zero();
+            }
+        });
+        fft.fft_rev(&mut out)?;
+        Ok(out)
+    }
+    /// Analytic signal via a streaming FIR Hilbert transformer of `order`
+    pub fn analytic_fir(&self, order: usize) -> anyhow::Result<Signal<T>> {
+        let mut out = HilbertFilter::new(self.sample_rate, order)?.process_and_finish(self.clone()).ok_or(anyhow!("Hilbert filter did not return values"))?;
+        out.time += self.time;
+        Ok(out.slice_time(self.time, self.end_time()))
+    }
+    /// Magnitude of an analytic signal
+    pub fn envelope(&self) -> Vec<T> {
+        self.abs()
+    }
+    /// Unwrapped phase of an analytic signal, in radians
+    pub fn instantaneous_phase(&self) -> Vec<T> {
+        let mut offset = 0.0;
+        let mut last = 0.0;
+        self.iter().map(|x| {
+            let phase: f64 = x.arg().as_();
+            let delta = phase - last;
+            if delta > PI {
+                offset -= 2.0 * PI;
+            } else if delta < -PI {
+                offset += 2.0 * PI;
+            }
+            last = phase;
+            T::from_f64(phase + offset).unwrap()
+        }).collect_vec()
+    }
+    /// Instantaneous frequency of an analytic signal in Hz, one sample shorter than the signal
+    pub fn instantaneous_frequency(&self) -> Vec<T> {
+        let scale = T::from_f64(self.sample_rate / (2.0 * PI)).unwrap();
+        self.iter().tuple_windows().map(|(a, b)| (b * a.conj()).arg() * scale).collect_vec()
+    }
+}
+
+impl<T: SignalType> RealSignal<T> {
+    /// `Signal::analytic` with the forward transform done as a half-length real FFT.
+    /// Odd lengths go through the complex FFT.
+    pub fn analytic(&self) -> anyhow::Result<Signal<T>> {
+        let len = self.len();
+        // The half-length FFT needs an even, non-empty signal
+        if len == 0 || len % 2 == 1 {
+            return self.to_complex().analytic();
+        }
+        let fft = RealFftInst::<T>::new(len)?;
+        let mut bins = vec![Complex::zero(); fft.bins()];
+        fft.fft_fwd(self, &mut bins)?;
+        // Negative frequencies stay zero, DC and Nyquist are kept as-is
+        let two = T::from_f64(2.0).unwrap();
+        let mut out = Signal::from_vec(self.sample_rate, vec![Complex::zero(); len]);
+        out.time = self.time;
+        out.meta = self.meta.clone();
+        out.iter_mut().zip(bins.iter().enumerate()).for_each(|(x, (idx, bin))| {
+            *x = if idx == 0 || 2 * idx == len { *bin } else { *bin * two };
+        });
+        RustFftInst::<T>::new(len).fft_rev(&mut out)?;
+        Ok(out)
+    }
+}
+
+#[test]
+fn test_hilbert() -> anyhow::Result<()> {
+    use crate::core::signal::FromFunction;
+    let freq = 10000.0;
+    let sig = RealSignal::from_function(96000.0, 9600, |x| f64::cos(freq * 2.0 * PI * x));
+    let reference = Signal::from_function(96000.0, 9600, |x| Complex::new(f64::cos(freq * 2.0 * PI * x), f64::sin(freq * 2.0 * PI * x)));
+
+    // FFT based, exact for a tone with an integer number of cycles
+    let analytic = sig.analytic()?;
+    assert!(analytic.iter().zip(reference.iter()).all(|(a, b)| (a - b).norm() < 1e-9));
+    assert!(analytic.envelope().iter().all(|x| (x - 1.0).abs() < 1e-9));
+    assert!(analytic.instantaneous_frequency().iter().all(|x| (x - freq).abs() < 1e-6));
+    let phase = analytic.instantaneous_phase();
+    assert!((phase[9599] - phase[0] - 9599.0 * freq / 96000.0 * 2.0 * PI).abs() < 1e-6);
+
+    // The real FFT path agrees with the complex one, also for odd lengths and empty signals
+    let complex = sig.to_complex().analytic()?;
+    assert!(analytic.iter().zip(complex.iter()).all(|(a, b)| (a - b).norm() < 1e-12));
+    let odd = RealSignal::from_vec(96000.0, sig[..9599].to_vec());
+    let complex = odd.to_complex().analytic()?;
+    assert!(odd.analytic()?.iter().zip(complex.iter()).all(|(a, b)| (a - b).norm() < 1e-12));
+    let mut empty = RealSignal::<f64>::from_vec(96000.0, vec![]);
+    empty.time = 42;
+    let analytic = empty.analytic()?;
+    assert_eq!((analytic.time, analytic.len()), (42, 0));
+
+    // FIR based, aligned on the input time axis, edges excluded
+    let analytic = sig.to_complex().analytic_fir(128)?;
+    assert_eq!((analytic.time, analytic.len()), (0, 9600));
+    let error = analytic[200..9400].iter().zip(reference[200..9400].iter()).map(|(a, b)| (a - b).norm()).fold(0.0, f64::max);
+    assert!(error < 1e-2, "fir error {error}");
+    Ok(())
+}
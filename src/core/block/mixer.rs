@@ -0,0 +1,233 @@
+use std::f64::consts::PI;
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use num::{Complex, Integer};
+
+use crate::{core::{block::filter::{fft_len, Filter}, r#gen::{chirp::euler, fir::fir_lpf}, tag::Tag}, prelude::*};
+
+/// Numerically controlled oscillator, phase is carried across calls
+#[derive(Clone, Debug)]
+pub struct Nco {
+    sample_rate: f64,
+    frequency: f64,
+    /// Radians, wrapped to `[0, 2pi)`
+    phase: f64,
+}
+impl Nco {
+    pub fn new(sample_rate: f64, frequency: f64) -> Nco {
+        Nco { sample_rate, frequency, phase: 0.0 }
+    }
+    /// Phase stays continuous across frequency changes
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(2.0 * PI);
+    }
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+    /// Move the phase by `samples` without generating them, e.g. over a gap
+    pub fn advance(&mut self, samples: i64) {
+        let step = 2.0 * PI * self.frequency / self.sample_rate;
+        self.phase = (self.phase + step * samples as f64).rem_euclid(2.0 * PI);
+    }
+    /// Next `len` samples of `e^(j*phase)`
+    pub fn generate<T: SignalType>(&mut self, len: usize) -> Vec<Complex<T>> {
+        let step = 2.0 * PI * self.frequency / self.sample_rate;
+        let out = (0..len).map(|idx| euler::<T>(T::from_f64((self.phase + step * idx as f64).rem_euclid(2.0 * PI)).unwrap())).collect_vec();
+        self.phase = (self.phase + step * len as f64).rem_euclid(2.0 * PI);
+        out
+    }
+}
+
+/// Multiplies a stream by the NCO output. The NCO follows stream `time`: phase starts at the
+/// first sample processed, and gaps or jumps in `time` move it by the samples skipped.
+pub struct Mixer {
+    nco: Nco,
+    /// Stream time the NCO phase belongs to
+    time: Option<i64>,
+}
+impl Mixer {
+    /// Shifts the spectrum by `frequency` (negative to move down)
+    pub fn new(sample_rate: f64, frequency: f64) -> Mixer {
+        Mixer { nco: Nco::new(sample_rate, frequency), time: None }
+    }
+    pub fn nco(&mut self) -> &mut Nco {
+        &mut self.nco
+    }
+    pub fn process<T: SignalType>(&mut self, mut data: Signal<T>) -> Signal<T> {
+        if let Some(time) = self.time {
+            self.nco.advance(data.time - time);
+        }
+        self.time = Some(data.end_time());
+        let lo = self.nco.generate::<T>(data.len());
+        data.iter_mut().zip(lo).for_each(|(x, lo)| *x *= lo);
+        data
+    }
+}
+
+impl<T: SignalType> Signal<T> {
+    /// Shift the spectrum by `frequency` Hz
+    pub fn mix(&self, frequency: f64) -> Signal<T> {
+        Mixer::new(self.sample_rate, frequency).process(self.clone())
+    }
+    /// Keep every `factor`-th sample on the stream time grid (`time % factor == 0`), no filtering
+    pub fn decimate(&self, factor: usize) -> Signal<T> {
+        let factor = factor.max(1) as i64;
+        let first = (factor - self.time.mod_floor(&factor)) % factor;
+        let mut out = Signal::from_vec(self.sample_rate / factor as f64, self.iter().skip(first as usize).step_by(factor as usize).cloned().collect_vec());
+        out.time = Integer::div_floor(&(self.time + first), &factor);
+        out.meta = self.meta.clone();
+        out.tags = self.tags.iter().map(|tag| Tag { time: Integer::div_ceil(&tag.time, &factor), ..tag.clone() }).collect_vec();
+        out
+    }
+    /// Insert `factor - 1` zeros after every sample, scaled by `factor` to keep the level after interpolation
+    pub fn zero_stuff(&self, factor: usize) -> Signal<T> {
+        let factor = factor.max(1);
+        let gain = T::from_usize(factor).unwrap();
+        let mut out = Signal::from_vec(self.sample_rate * factor as f64, self.iter().flat_map(|x| {
+            std::iter::once(*x * gain).chain(std::iter::repeat_n(Complex::default(), factor - 1))
+        }).collect_vec());
+        out.time = self.time * factor as i64;
+        out.meta = self.meta.clone();
+        out.tags = self.tags.iter().map(|tag| Tag { time: tag.time * factor as i64, ..tag.clone() }).collect_vec();
+        out
+    }
+}
+
+/// Unit DC gain low-pass for the converters, `cutoff` normalized to Nyquist
+fn converter_filter<T: SignalType>(cutoff: f64, order: usize, sample_rate: f64) -> anyhow::Result<Filter<T>> {
+    let kern = fir_lpf::<T>(cutoff, order).ok_or(anyhow!("Failed to construct converter filter"))?;
+    // Undo the `1/sqrt(fft_len)` of the filter too
+    let gain = kern.iter().fold(T::zero(), |a, b| a + *b) / T::from_usize(fft_len(kern.len())).unwrap().sqrt();
+    Filter::new(Signal::from_vec(sample_rate, kern.iter().map(|x| *x / gain).collect_vec()))
+}
+
+/// Passband -> baseband: mix by `-center_frequency`, low-pass and decimate
+pub struct DownConverter<T: SignalType> {
+    mixer: Mixer,
+    filter: Filter<T>,
+    decimation: usize,
+    center_frequency: f64,
+}
+impl<T: SignalType> DownConverter<T> {
+    /// `bandwidth` is the two-sided bandwidth kept around `center_frequency`
+    pub fn new(sample_rate: f64, center_frequency: f64, bandwidth: f64, decimation: usize, order: usize) -> anyhow::Result<DownConverter<T>> {
+        if bandwidth * decimation as f64 > sample_rate {
+            return Err(anyhow!("Bandwidth {bandwidth} Hz aliases after decimating {sample_rate} Hz by {decimation}"));
+        }
+        Ok(DownConverter {
+            mixer: Mixer::new(sample_rate, -center_frequency),
+            filter: converter_filter(bandwidth / sample_rate, order, sample_rate)?,
+            decimation,
+            center_frequency,
+        })
+    }
+    pub fn process(&mut self, data: Signal<T>) -> Option<Signal<T>> {
+        let mixed = self.mixer.process(data);
+        let filtered = self.filter.process(mixed)?;
+        Some(downconverted(filtered, self.decimation, self.center_frequency))
+    }
+    pub fn finish(mut self) -> Option<Signal<T>> {
+        self.flush()
+    }
+    pub fn flush(&mut self) -> Option<Signal<T>> {
+        Some(downconverted(self.filter.flush()?, self.decimation, self.center_frequency))
+    }
+}
+
+fn downconverted<T: SignalType>(filtered: Signal<T>, decimation: usize, center_frequency: f64) -> Signal<T> {
+    let mut out = filtered.decimate(decimation);
+    let mut meta = out.meta.clone().unwrap_or_default();
+    meta.center_frequency = Some(meta.center_frequency.unwrap_or(0.0) + center_frequency);
+    out.meta = Some(meta);
+    out
+}
+
+/// Baseband -> passband: zero-stuff, low-pass and mix by `center_frequency`
+pub struct UpConverter<T: SignalType> {
+    mixer: Mixer,
+    filter: Filter<T>,
+    interpolation: usize,
+    center_frequency: f64,
+}
+impl<T: SignalType> UpConverter<T> {
+    /// `sample_rate` is the baseband rate, output runs at `sample_rate * interpolation`
+    pub fn new(sample_rate: f64, center_frequency: f64, interpolation: usize, order: usize) -> anyhow::Result<UpConverter<T>> {
+        let out_rate = sample_rate * interpolation as f64;
+        Ok(UpConverter {
+            mixer: Mixer::new(out_rate, center_frequency),
+            filter: converter_filter(1.0 / interpolation as f64, order, out_rate)?,
+            interpolation,
+            center_frequency,
+        })
+    }
+    pub fn process(&mut self, data: Signal<T>) -> Option<Signal<T>> {
+        let filtered = self.filter.process(data.zero_stuff(self.interpolation))?;
+        Some(upconverted(&mut self.mixer, filtered, self.center_frequency))
+    }
+    pub fn finish(mut self) -> Option<Signal<T>> {
+        self.flush()
+    }
+    pub fn flush(&mut self) -> Option<Signal<T>> {
+        Some(upconverted(&mut self.mixer, self.filter.flush()?, self.center_frequency))
+    }
+}
+
+fn upconverted<T: SignalType>(mixer: &mut Mixer, filtered: Signal<T>, center_frequency: f64) -> Signal<T> {
+    let mut out = mixer.process(filtered);
+    if let Some(meta) = out.meta.as_mut() {
+        meta.center_frequency = meta.center_frequency.map(|fc| fc - center_frequency).filter(|fc| *fc != 0.0);
+    }
+    out
+}
+
+#[test]
+fn test_mixer() -> anyhow::Result<()> {
+    use crate::core::signal::FromFunction;
+    let tone = |freq: f64| move |x: f64| Complex::new(f64::cos(freq * 2.0 * PI * x), f64::sin(freq * 2.0 * PI * x));
+    let sig = Signal::from_function(192000.0, 19200, tone(25000.0));
+
+    // Streaming mixer keeps phase continuity across chunks
+    let batch = sig.mix(-24000.0);
+    let mut mixer = Mixer::new(192000.0, -24000.0);
+    let streamed = sig.split_chunks(1000).into_iter().flat_map(|chunk| mixer.process(chunk).to_vec()).collect_vec();
+    assert!(streamed.iter().zip(batch.iter()).all(|(a, b)| (a - b).norm() < 1e-9));
+    // Over a gap the phase follows stream time
+    let mut mixer = Mixer::new(192000.0, -24000.0);
+    let chunks = sig.split_chunks(999);
+    for chunk in chunks.iter().filter(|x| x.time != 2997) {
+        let mixed = mixer.process(chunk.clone());
+        assert!(mixed.iter().zip(batch.slice_time(mixed.time, mixed.end_time()).iter()).all(|(a, b)| (a - b).norm() < 1e-9));
+    }
+    let reference = Signal::from_function(192000.0, 19200, tone(1000.0));
+    assert!(batch.iter().zip(reference.iter()).all(|(a, b)| (a - b).norm() < 1e-6));
+
+    // Down-conversion to a 24 kHz baseband
+    let mut down = DownConverter::<f64>::new(192000.0, 24000.0, 8000.0, 8, 512)?;
+    let mut baseband = down.process(sig.clone()).unwrap();
+    baseband.append(&mut down.finish().unwrap());
+    assert_eq!(baseband.sample_rate, 24000.0);
+    assert_eq!(baseband.meta.as_ref().unwrap().center_frequency, Some(24000.0));
+    let mid = baseband.slice(200..2200);
+    assert!(mid.iter().all(|x| (x.norm() - 1.0).abs() < 1e-2));
+    let freq = mid.iter().tuple_windows().map(|(a, b)| (b * a.conj()).arg()).sum::<f64>() / (mid.len() - 1) as f64 * 24000.0 / (2.0 * PI);
+    assert!((freq - 1000.0).abs() < 1.0, "baseband frequency {freq}");
+
+    // Up-conversion back to 25 kHz
+    let mut up = UpConverter::<f64>::new(24000.0, 24000.0, 8, 256)?;
+    let mut passband = up.process(mid).unwrap();
+    passband.append(&mut up.finish().unwrap());
+    assert_eq!(passband.sample_rate, 192000.0);
+    assert_eq!(passband.meta.as_ref().unwrap().center_frequency, None);
+    let mid = passband.slice(2000..14000);
+    assert!(mid.iter().all(|x| (x.norm() - 1.0).abs() < 2e-2));
+    let freq = mid.iter().tuple_windows().map(|(a, b)| (b * a.conj()).arg()).sum::<f64>() / (mid.len() - 1) as f64 * 192000.0 / (2.0 * PI);
+    assert!((freq - 25000.0).abs() < 1.0, "passband frequency {freq}");
+    Ok(())
+}
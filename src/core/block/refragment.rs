@@ -4,26 +4,65 @@ use itertools::Itertools;
 use num::Zero;
 use plotters::style::AsRelative;
 
-use crate::{core::signal::FromFunction, prelude::*};
+use crate::{core::{multi_signal::MultiSignal, signal::FromFunction, stream::Discontinuity, tag::TagBuffer}, prelude::*};
 
 pub struct Refragmenter<T: SignalType> {
     time: AtomicI64,
     overflow: Signal<T>,
     frag_len: usize,
+    max_gap: usize,
+    tags: TagBuffer,
 }
 impl<T:SignalType> Refragmenter<T> {
     pub fn new(sample_rate: f64, frag_len: usize) -> Refragmenter<T> {
-        Refragmenter { time: AtomicI64::new(0), overflow: Signal::new(sample_rate), frag_len }
+        Refragmenter { time: AtomicI64::new(0), overflow: Signal::new(sample_rate), frag_len, max_gap: frag_len, tags: TagBuffer::new() }
     }
+    /// Longest `Gap` that is zero-filled, `frag_len` by default
+    pub fn with_max_gap(mut self, max_gap: usize) -> Self {
+        self.max_gap = max_gap;
+        self
+    }
+    /// A `Gap` marker on `sig` is zero-filled up to `max_gap` samples. A longer one discards the
+    /// partial fragment like a `Reset` and moves the output time past the gap instead.
     pub fn push(&mut self, sig: &mut Signal<T>) {
+        match sig.stream.and_then(|info| info.discontinuity) {
+            Some(Discontinuity::Gap { samples }) if samples.max(0) as usize <= self.max_gap => {
+                let len = self.overflow.len() + samples.max(0) as usize;
+                self.overflow.resize(len, num::Complex::<T>::zero());
+            }
+            Some(Discontinuity::Gap { samples }) => {
+                let time = self.time.fetch_add(self.overflow.len() as i64 + samples, std::sync::atomic::Ordering::Relaxed);
+                self.tags.discard_from(time);
+                self.overflow.clear();
+            }
+            Some(Discontinuity::Reset) => self.overflow.clear(),
+            None => {}
+        }
+        // Output time counts pushed samples from 0
+        let first = self.time.load(std::sync::atomic::Ordering::Relaxed) + self.overflow.len() as i64;
+        self.tags.discard_from(first);
+        self.tags.push(sig, |time| first + time - sig.time);
+        self.overflow.meta = sig.meta.clone();
         self.overflow.append(sig);
     }
+    /// Samples waiting for a full fragment
+    pub fn pending(&self) -> usize {
+        self.overflow.len()
+    }
     pub fn finish(mut self) -> Option<Signal<T>> {
+        self.flush()
+    }
+    /// Emit everything pending, zero-padded to a whole number of fragments, leaving the
+    /// refragmenter empty. More than one fragment is pending when it was not drained after `push`.
+    pub fn flush(&mut self) -> Option<Signal<T>> {
         let len = self.overflow.len();
         if len != 0 {
-            self.overflow.append(&mut vec![num::Complex::<T>::zero(); self.frag_len-len]);
-            self.overflow.time = self.time.fetch_add(self.frag_len as i64, std::sync::atomic::Ordering::Relaxed);
-            Some(self.overflow)
+            let padded = len.next_multiple_of(self.frag_len);
+            self.overflow.resize(padded, num::Complex::<T>::zero());
+            self.overflow.time = self.time.fetch_add(padded as i64, std::sync::atomic::Ordering::Relaxed);
+            self.tags.attach_all(&mut self.overflow);
+            let empty = Signal::new(self.overflow.sample_rate);
+            Some(std::mem::replace(&mut self.overflow, empty))
         } else {
             None
         }
@@ -35,7 +74,9 @@ impl<T:SignalType> Iterator for &mut Refragmenter<T> {
     fn next(&mut self) -> Option<Self::Item> {
         if self.overflow.len() >= self.frag_len{
             let mut sig = Signal::from_vec(self.overflow.sample_rate, self.overflow.drain(0..self.frag_len).collect_vec());
+            sig.meta = self.overflow.meta.clone();
             sig.time = self.time.fetch_add(self.frag_len as i64, std::sync::atomic::Ordering::Relaxed);
+            self.tags.attach(&mut sig);
             Some(sig)
         } else {
             None
@@ -43,6 +84,46 @@ impl<T:SignalType> Iterator for &mut Refragmenter<T> {
     }
 }
 
+/// Refragments every channel of a `MultiSignal` in lockstep, with the same `Gap`, `Reset` and
+/// tag handling as `Refragmenter`
+pub struct MultiRefragmenter<T: SignalType> {
+    channels: Vec<Refragmenter<T>>,
+}
+impl<T:SignalType> MultiRefragmenter<T> {
+    pub fn new(sample_rate: f64, n_channels: usize, frag_len: usize) -> MultiRefragmenter<T> {
+        MultiRefragmenter { channels: (0..n_channels).map(|_| Refragmenter::new(sample_rate, frag_len)).collect_vec() }
+    }
+    pub fn push(&mut self, sig: &mut MultiSignal<T>) -> anyhow::Result<()> {
+        if sig.n_channels() != self.channels.len() {
+            return Err(anyhow::anyhow!("Channel count mismatch: {} vs {}", self.channels.len(), sig.n_channels()));
+        }
+        let empty = MultiSignal::new(sig.sample_rate, sig.n_channels());
+        let channels = std::mem::replace(sig, empty).into_channels();
+        self.channels.iter_mut().zip(channels).for_each(|(refrag, mut ch)| refrag.push(&mut ch));
+        Ok(())
+    }
+    /// Samples per channel waiting for a full fragment
+    pub fn pending(&self) -> usize {
+        self.channels.first().map_or(0, |refrag| refrag.pending())
+    }
+    pub fn finish(mut self) -> Option<MultiSignal<T>> {
+        self.flush()
+    }
+    /// Zero-pad and emit the pending samples, see `Refragmenter::flush`
+    pub fn flush(&mut self) -> Option<MultiSignal<T>> {
+        let channels: Option<Vec<_>> = self.channels.iter_mut().map(|refrag| refrag.flush()).collect();
+        MultiSignal::from_channels(channels?).ok()
+    }
+}
+impl<T:SignalType> Iterator for &mut MultiRefragmenter<T> {
+    type Item = MultiSignal<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channels: Option<Vec<_>> = self.channels.iter_mut().map(|mut refrag| refrag.next()).collect();
+        MultiSignal::from_channels(channels?).ok()
+    }
+}
+
 #[test]
 fn test_refrag() -> anyhow::Result<()> {
     init_tracing();
@@ -0,0 +1,182 @@
+use anyhow::anyhow;
+use itertools::Itertools;
+use num::{Complex, Integer, Zero};
+
+use crate::{core::{r#gen::fir::{hamming, sinc}, signal::SignalMeta, tag::TagBuffer}, prelude::*};
+
+/// Rational `up/down` polyphase resampler. Works chunk-by-chunk, `time` of the output is
+/// on the output-rate axis and aligned with the input (the prototype delay is absorbed as latency).
+pub struct Resampler<T: SignalType> {
+    up: usize,
+    down: usize,
+    out_rate: f64,
+    /// `phases[p][k] = h[p + k*up]`
+    phases: Vec<Vec<T>>,
+    /// Input samples, `buffer[0]` is input sample `buffer_start`
+    buffer: Vec<Complex<T>>,
+    buffer_start: i64,
+    /// Next output sample index
+    next_out: i64,
+    /// Output time of output sample 0
+    time_offset: Option<i64>,
+    /// Prototype center, in upsampled samples
+    center: i64,
+    submitted: usize,
+    meta: Option<SignalMeta>,
+    tags: TagBuffer,
+}
+
+impl<T: SignalType> Resampler<T> {
+    /// `taps_per_phase` trades stop-band rejection for cost, 16-32 is a good range
+    pub fn new(in_rate: f64, up: usize, down: usize, taps_per_phase: usize) -> anyhow::Result<Resampler<T>> {
+        if up == 0 || down == 0 || taps_per_phase == 0 {
+            return Err(anyhow!("Invalid resampler ratio {up}/{down} with {taps_per_phase} taps per phase"));
+        }
+        let gcd = up.gcd(&down);
+        let (up, down) = (up / gcd, down / gcd);
+
+        // Prototype low-pass at the upsampled rate, cut off at the lower of the two Nyquist rates.
+        // An even length windowed sinc is symmetric around `len/2` once the first tap is dropped.
+        let len = (taps_per_phase * usize::max(up, down)) / 2 * 2 + 2;
+        let window = hamming::<T>(len).ok_or(anyhow!("Failed to construct resampler window"))?;
+        let proto = sinc::<T>(1.0 / usize::max(up, down) as f64, len).ok_or(anyhow!("Failed to construct resampler prototype"))?
+            .iter().zip(window.iter()).skip(1).map(|(a, b)| *a * *b).collect_vec();
+        let gain = T::from_usize(up).unwrap() / proto.iter().fold(T::zero(), |a, b| a + *b);
+        let taps = proto.len().div_ceil(up);
+        let phases = (0..up).map(|p| {
+            (0..taps).map(|k| proto.get(p + k * up).map(|x| *x * gain).unwrap_or_default()).collect_vec()
+        }).collect_vec();
+
+        Ok(Resampler {
+            up,
+            down,
+            out_rate: in_rate * up as f64 / down as f64,
+            phases,
+            buffer: vec![Complex::zero(); taps - 1],
+            buffer_start: -(taps as i64 - 1),
+            next_out: 0,
+            time_offset: None,
+            center: (proto.len() / 2) as i64,
+            submitted: 0,
+            meta: None,
+            tags: TagBuffer::new(),
+        })
+    }
+    /// Integer rates only, reduced to the smallest `up/down` ratio. See `FarrowResampler` for arbitrary ratios.
+    pub fn from_rates(in_rate: f64, out_rate: f64) -> anyhow::Result<Resampler<T>> {
+        if in_rate.fract() != 0.0 || out_rate.fract() != 0.0 || in_rate <= 0.0 || out_rate <= 0.0 {
+            return Err(anyhow!("Rational resampling requires positive integer rates, got {in_rate} -> {out_rate}"));
+        }
+        Self::new(in_rate, out_rate as usize, in_rate as usize, 24)
+    }
+    pub fn ratio(&self) -> (usize, usize) {
+        (self.up, self.down)
+    }
+    pub fn out_rate(&self) -> f64 {
+        self.out_rate
+    }
+    /// Input samples held back before an output can be produced
+    pub fn latency(&self) -> usize {
+        (self.center as usize).div_ceil(self.up)
+    }
+    fn taps(&self) -> usize {
+        self.phases[0].len()
+    }
+    fn run(&mut self) -> Signal<T> {
+        let buffer_end = self.buffer_start + self.buffer.len() as i64;
+        let (up, down) = (self.up as i64, self.down as i64);
+        let mut out = Signal::new(self.out_rate);
+        out.time = self.time_offset.unwrap_or(0) + self.next_out;
+        out.meta = self.meta.clone();
+        loop {
+            let n = self.next_out * down + self.center;
+            let (idx, phase) = (Integer::div_floor(&n, &up), n.mod_floor(&up) as usize);
+            if idx >= buffer_end {
+                break;
+            }
+            let pos = (idx - self.buffer_start) as usize;
+            let sum = self.phases[phase].iter().enumerate().fold(Complex::zero(), |acc, (k, h)| acc + self.buffer[pos - k] * *h);
+            out.push(sum);
+            self.next_out += 1;
+        }
+        // Keep `taps - 1` samples of history for the next output
+        let next_idx = Integer::div_floor(&(self.next_out * down + self.center), &up);
+        let keep_from = usize::min((next_idx - (self.taps() as i64 - 1) - self.buffer_start).max(0) as usize, self.buffer.len());
+        self.buffer.drain(0..keep_from);
+        self.buffer_start += keep_from as i64;
+        out
+    }
+    pub fn process(&mut self, data: Signal<T>) -> Signal<T> {
+        if self.time_offset.is_none() {
+            self.time_offset = Some((data.time as f64 * self.up as f64 / self.down as f64).round() as i64);
+        }
+        self.meta = data.meta.clone();
+        let (offset, first, ratio) = (self.time_offset.unwrap_or(0), self.submitted as i64, self.up as f64 / self.down as f64);
+        self.tags.push(&data, |time| offset + ((first + time - data.time) as f64 * ratio).round() as i64);
+        self.submitted += data.len();
+        self.buffer.extend_from_slice(&data);
+        let mut out = self.run();
+        self.tags.attach(&mut out);
+        out
+    }
+    /// Flush the filter tail. Output ends at the resampled end of the input.
+    pub fn finish(mut self) -> Signal<T> {
+        self.flush()
+    }
+    /// `finish` for callers that cannot give up ownership, the resampler must not be used afterwards
+    pub fn flush(&mut self) -> Signal<T> {
+        let taps = self.taps();
+        self.buffer.extend(std::iter::repeat_n(Complex::zero(), taps));
+        let mut out = self.run();
+        let end = self.time_offset.unwrap_or(0) + (self.submitted * self.up).div_ceil(self.down) as i64;
+        let len = (end - out.time).max(0) as usize;
+        out.truncate(len);
+        self.tags.attach_all(&mut out);
+        out
+    }
+    pub fn process_and_finish(mut self, data: Signal<T>) -> Signal<T> {
+        let mut out = self.process(data);
+        let mut tail = self.finish();
+        out.append(&mut tail);
+        out
+    }
+}
+
+impl<T: SignalType> Signal<T> {
+    /// Batch resample to `new_rate` (integer rates). The output spans the same time as the input.
+    pub fn resample(&self, new_rate: f64) -> anyhow::Result<Signal<T>> {
+        Ok(Resampler::from_rates(self.sample_rate, new_rate)?.process_and_finish(self.clone()))
+    }
+}
+
+#[test]
+fn test_resample() -> anyhow::Result<()> {
+    use crate::core::signal::FromFunction;
+    let tone = |x: f64| Complex::new(f64::cos(1000.0 * core::f64::consts::PI * 2.0 * x), f64::sin(1000.0 * core::f64::consts::PI * 2.0 * x));
+    let sig = Signal::from_function(192000.0, 19200, tone);
+
+    // 192 kHz -> 12 kHz, output is aligned with the input on the time axis
+    let down = sig.resample(12000.0)?;
+    assert_eq!((down.sample_rate, down.time, down.len()), (12000.0, 0, 1200));
+    let reference = Signal::from_function(12000.0, 1200, tone);
+    let error = down[100..1100].iter().zip(reference[100..1100].iter()).map(|(a, b)| (a - b).norm()).fold(0.0, f64::max);
+    assert!(error < 1e-2, "max error {error}");
+
+    // 12 kHz -> 44.1 kHz (147/40)
+    let up = down.resample(44100.0)?;
+    assert_eq!(up.len(), 4410);
+
+    // Streaming in odd chunk sizes matches batch
+    let mut resampler = Resampler::<f64>::from_rates(192000.0, 12000.0)?;
+    let mut streamed = Signal::new(12000.0);
+    for chunk in sig.split_chunks(1000) {
+        let out = resampler.process(chunk);
+        assert_eq!(out.time, streamed.end_time());
+        streamed.append(&mut out.to_vec());
+    }
+    let mut tail = resampler.finish();
+    streamed.append(&mut tail);
+    assert_eq!(streamed.len(), 1200);
+    assert!(streamed.iter().zip(down.iter()).all(|(a, b)| (a - b).norm() < 1e-9));
+    Ok(())
+}
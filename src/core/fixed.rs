@@ -0,0 +1,284 @@
+use std::ops::{Add, Deref, DerefMut, Mul, Neg, Sub};
+
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use log::warn;
+use num::{Complex, One, PrimInt, Signed};
+
+use crate::{core::signal::SignalMeta, prelude::*};
+
+/// Integer storage for `Q` samples. `Acc` is wide enough to hold any product of two samples
+/// plus accumulation guard bits, so filters only saturate when writing results back.
+pub trait FixedStorage: PrimInt + Signed + std::fmt::Debug + Default + std::hash::Hash + Send + Sync + 'static {
+    type Acc: PrimInt + Signed + std::fmt::Debug;
+    fn widen(self) -> Self::Acc;
+    /// Clamp to `[MIN, MAX]`
+    fn saturate(acc: Self::Acc) -> Self;
+}
+macro_rules! fixed_storage {
+    ($s:ty, $acc:ty) => {
+        impl FixedStorage for $s {
+            type Acc = $acc;
+            fn widen(self) -> $acc {
+                self as $acc
+            }
+            fn saturate(acc: $acc) -> $s {
+                acc.clamp(<$s>::MIN as $acc, <$s>::MAX as $acc) as $s
+            }
+        }
+    };
+}
+fixed_storage!(i16, i64);
+fixed_storage!(i32, i128);
+
+/// How fractional bits are dropped after a multiply
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// Arithmetic shift right (round towards -inf), what most DSP cores do by default
+    #[default]
+    Truncate,
+    /// Add half an LSB before shifting
+    Nearest,
+}
+
+/// Shift an accumulator right by `bits`, rounding as requested
+pub fn round_shift<S: FixedStorage>(acc: S::Acc, bits: u32, rounding: Rounding) -> S::Acc {
+    if bits == 0 {
+        return acc;
+    }
+    let acc = match rounding {
+        Rounding::Truncate => acc,
+        Rounding::Nearest => acc + (S::Acc::one() << (bits as usize - 1)),
+    };
+    acc >> bits as usize
+}
+
+/// `round_shift` and saturate into `S`
+pub fn requantize<S: FixedStorage>(acc: S::Acc, bits: u32, rounding: Rounding) -> S {
+    S::saturate(round_shift::<S>(acc, bits, rounding))
+}
+
+/// Fixed-point number with `FRAC` fractional bits stored in `S`, e.g. `Q<i16, 15>` is Q15.
+/// Arithmetic saturates instead of wrapping.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Q<S, const FRAC: u32>(pub S);
+
+pub type Q15 = Q<i16, 15>;
+pub type Q31 = Q<i32, 31>;
+
+impl<S: FixedStorage, const FRAC: u32> Q<S, FRAC> {
+    pub fn from_raw(raw: S) -> Self {
+        Q(raw)
+    }
+    pub fn raw(self) -> S {
+        self.0
+    }
+    /// Value of one LSB
+    pub fn resolution() -> f64 {
+        0.5_f64.powi(FRAC as i32)
+    }
+    pub fn max_value() -> Self {
+        Q(S::max_value())
+    }
+    pub fn min_value() -> Self {
+        Q(S::min_value())
+    }
+    /// Round to nearest and saturate, NaN maps to zero
+    pub fn from_f64(value: f64) -> Self {
+        let scaled = (value / Self::resolution()).round();
+        let min = S::min_value().to_f64().unwrap();
+        let max = S::max_value().to_f64().unwrap();
+        Q(S::from(scaled.clamp(min, max)).unwrap_or_default())
+    }
+    /// `None` if `value` is outside the representable range
+    pub fn checked_from_f64(value: f64) -> Option<Self> {
+        let scaled = (value / Self::resolution()).round();
+        S::from(scaled).map(Q)
+    }
+    pub fn to_f64(self) -> f64 {
+        self.0.to_f64().unwrap() * Self::resolution()
+    }
+    pub fn saturating_mul(self, rhs: Self, rounding: Rounding) -> Self {
+        Q(requantize::<S>(self.0.widen() * rhs.0.widen(), FRAC, rounding))
+    }
+    /// Multiply by `2^shift` (negative shifts right), saturating
+    pub fn shift(self, shift: i32) -> Self {
+        let bits = S::zero().count_zeros();
+        if shift >= 0 {
+            // `Acc` has at least `bits` bits of headroom, any non-zero sample shifted further saturates
+            if (shift as u32) < bits {
+                Q(S::saturate(self.0.widen() << shift as usize))
+            } else if self.0.is_negative() {
+                Self::min_value()
+            } else if self.0.is_positive() {
+                Self::max_value()
+            } else {
+                self
+            }
+        } else {
+            // Shifting by at least the width of `S` leaves only the sign
+            Q(requantize::<S>(self.0.widen(), shift.unsigned_abs().min(bits), Rounding::Truncate))
+        }
+    }
+}
+impl<S: FixedStorage, const FRAC: u32> Add for Q<S, FRAC> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Q(S::saturate(self.0.widen() + rhs.0.widen()))
+    }
+}
+impl<S: FixedStorage, const FRAC: u32> Sub for Q<S, FRAC> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Q(S::saturate(self.0.widen() - rhs.0.widen()))
+    }
+}
+/// Truncating, use `saturating_mul` to choose the rounding
+impl<S: FixedStorage, const FRAC: u32> Mul for Q<S, FRAC> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        self.saturating_mul(rhs, Rounding::Truncate)
+    }
+}
+/// `-MIN` saturates to `MAX`
+impl<S: FixedStorage, const FRAC: u32> Neg for Q<S, FRAC> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Q(S::saturate(-self.0.widen()))
+    }
+}
+impl<S: FixedStorage, const FRAC: u32> std::fmt::Display for Q<S, FRAC> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_f64())
+    }
+}
+
+/// Fixed-point counterpart of `Signal<T>` with the same `sample_rate`/`time` semantics,
+/// for bit-accurate simulation of integer firmware.
+#[derive(Clone, Debug)]
+pub struct FixedSignal<S, const FRAC: u32> {
+    pub sample_rate: f64,
+    pub time: i64,
+    pub meta: Option<SignalMeta>,
+    samples: Vec<Complex<Q<S, FRAC>>>,
+}
+
+impl<S: FixedStorage, const FRAC: u32> FixedSignal<S, FRAC> {
+    pub fn new(sample_rate: f64) -> Self {
+        Self::from_vec(sample_rate, Vec::new())
+    }
+    pub fn from_vec(sample_rate: f64, samples: Vec<Complex<Q<S, FRAC>>>) -> Self {
+        FixedSignal { sample_rate, time: 0, meta: None, samples }
+    }
+    /// Quantize, saturating out of range samples (a warning reports how many)
+    pub fn from_signal<T: SignalType>(signal: &Signal<T>) -> Self {
+        let mut clipped = 0;
+        let mut quantize = |x: T| {
+            let x: f64 = x.as_();
+            if Q::<S, FRAC>::checked_from_f64(x).is_none() {
+                clipped += 1;
+            }
+            Q::from_f64(x)
+        };
+        let samples = signal.iter().map(|x| Complex::new(quantize(x.re), quantize(x.im))).collect_vec();
+        if clipped > 0 {
+            warn!("{clipped} values saturated converting to Q{FRAC}");
+        }
+        FixedSignal { sample_rate: signal.sample_rate, time: signal.time, meta: signal.meta.clone(), samples }
+    }
+    pub fn to_signal<T: SignalType>(&self) -> Signal<T> {
+        let samples = self.samples.iter().map(|x| Complex::new(T::from_f64(x.re.to_f64()).unwrap(), T::from_f64(x.im.to_f64()).unwrap())).collect_vec();
+        let mut out = Signal::from_vec(self.sample_rate, samples);
+        out.time = self.time;
+        out.meta = self.meta.clone();
+        out
+    }
+    pub fn into_vec(self) -> Vec<Complex<Q<S, FRAC>>> {
+        self.samples
+    }
+    fn zip_with(&self, rhs: &Self, op: impl Fn(Q<S, FRAC>, Q<S, FRAC>) -> Q<S, FRAC>) -> Result<Self> {
+        if self.sample_rate != rhs.sample_rate || self.time != rhs.time || self.len() != rhs.len() {
+            return Err(anyhow!("Fixed-point operands must share sample rate, time and length"));
+        }
+        let samples = self.iter().zip(rhs.iter()).map(|(a, b)| Complex::new(op(a.re, b.re), op(a.im, b.im))).collect_vec();
+        Ok(FixedSignal { samples, ..self.clone() })
+    }
+    /// Sample-wise saturating sum
+    pub fn saturating_add(&self, rhs: &Self) -> Result<Self> {
+        self.zip_with(rhs, |a, b| a + b)
+    }
+    pub fn saturating_sub(&self, rhs: &Self) -> Result<Self> {
+        self.zip_with(rhs, |a, b| a - b)
+    }
+    /// Multiply every component by a real gain
+    pub fn scale(&self, gain: Q<S, FRAC>, rounding: Rounding) -> Self {
+        let samples = self.iter().map(|x| Complex::new(x.re.saturating_mul(gain, rounding), x.im.saturating_mul(gain, rounding))).collect_vec();
+        FixedSignal { samples, ..self.clone() }
+    }
+    /// Multiply by `2^shift`, saturating
+    pub fn shift(&self, shift: i32) -> Self {
+        let samples = self.iter().map(|x| Complex::new(x.re.shift(shift), x.im.shift(shift))).collect_vec();
+        FixedSignal { samples, ..self.clone() }
+    }
+}
+
+impl<S, const FRAC: u32> Deref for FixedSignal<S, FRAC> {
+    type Target = Vec<Complex<Q<S, FRAC>>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.samples
+    }
+}
+impl<S, const FRAC: u32> DerefMut for FixedSignal<S, FRAC> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.samples
+    }
+}
+
+#[test]
+fn test_fixed() -> anyhow::Result<()> {
+    // Q15 conversion and saturation
+    assert_eq!(Q15::from_f64(0.5).raw(), 16384);
+    assert_eq!(Q15::from_f64(1.0).raw(), i16::MAX);
+    assert_eq!(Q15::from_f64(-1.0).raw(), i16::MIN);
+    assert!(Q15::checked_from_f64(1.0).is_none());
+    assert_eq!(Q15::from_f64(f64::NAN).raw(), 0);
+    assert_eq!((Q15::from_f64(0.75) + Q15::from_f64(0.5)).raw(), i16::MAX);
+    assert_eq!((Q15::from_f64(-0.75) - Q15::from_f64(0.5)).raw(), i16::MIN);
+    assert_eq!((-Q15::min_value()).raw(), i16::MAX);
+    assert_eq!((Q15::min_value() * Q15::min_value()).raw(), i16::MAX);
+    assert_eq!((Q15::from_f64(0.5) * Q15::from_f64(-0.25)).to_f64(), -0.125);
+
+    // Truncation vs rounding of the dropped bits: 3 * 0.5 LSB
+    let lsb = Q15::from_raw(3);
+    let half = Q15::from_f64(0.5);
+    assert_eq!(lsb.saturating_mul(half, Rounding::Truncate).raw(), 1);
+    assert_eq!(lsb.saturating_mul(half, Rounding::Nearest).raw(), 2);
+    assert_eq!((-lsb).saturating_mul(half, Rounding::Truncate).raw(), -2);
+    assert_eq!(Q31::from_f64(0.25).shift(2).raw(), i32::MAX);
+    // Shifts past the accumulator width saturate by sign instead of wrapping
+    assert_eq!(half.shift(50), Q15::max_value());
+    assert_eq!((-half).shift(64), Q15::min_value());
+    assert_eq!(Q31::from_raw(-1).shift(i32::MAX), Q31::min_value());
+    assert_eq!(Q15::from_raw(0).shift(100).raw(), 0);
+    assert_eq!(half.shift(-70).raw(), 0);
+    assert_eq!((-lsb).shift(i32::MIN).raw(), -1);
+
+    // Signal round trip stays within half an LSB
+    let mut sig = Signal::from_vec(8000.0, (0..100).map(|x| Complex::new(x as f64 / 100.0 - 0.5, 0.3)).collect_vec());
+    sig.time = 42;
+    let fixed = FixedSignal::<i16, 15>::from_signal(&sig);
+    assert_eq!(fixed.time, 42);
+    let back = fixed.to_signal::<f64>();
+    assert!(back.iter().zip(sig.iter()).all(|(a, b)| (a - b).norm() <= Q15::resolution()));
+
+    let sum = fixed.saturating_add(&fixed)?;
+    assert_eq!(sum[0].re, Q15::min_value());
+    assert_eq!(sum[99].re, Q15::from_raw(2 * fixed[99].re.raw()));
+    assert_eq!(fixed.shift(1)[0].re, sum[0].re);
+    assert_eq!(fixed.scale(half, Rounding::Truncate)[50].im, Q15::from_raw(fixed[50].im.raw() >> 1));
+    let mut shifted = fixed.clone();
+    shifted.time += 1;
+    assert!(fixed.saturating_sub(&shifted).is_err());
+    Ok(())
+}
@@ -10,7 +10,7 @@ function WINDOW = ba_hamming(width)
 end
 */
 
-use crate::{plot::time::{plot, plot_complex}, prelude::*};
+use crate::{core::real_signal::RealSignal, plot::time::{plot, plot_complex}, prelude::*};
 use std::f64::consts::PI;
 
 use log::warn;
@@ -138,6 +138,21 @@ pub fn fir_hpf<T: SignalType>(cutoff: f64, order: usize) -> Option<Vec<T>> {
     return fir_bpf(cutoff, 1.0, order);
 }
 
+impl<T: SignalType> RealSignal<T> {
+    /// cutoff is real frequency
+    pub fn fir_lpf(sample_rate: f64, cutoff: f64, order: usize) -> Option<RealSignal<T>> {
+        Some(RealSignal::from_vec(sample_rate, fir_lpf(cutoff/sample_rate*2.0, order)?))
+    }
+    /// cutoff is real frequency
+    pub fn fir_hpf(sample_rate: f64, cutoff: f64, order: usize) -> Option<RealSignal<T>> {
+        Some(RealSignal::from_vec(sample_rate, fir_hpf(cutoff/sample_rate*2.0, order)?))
+    }
+    /// cutoffs are real frequency
+    pub fn fir_bpf(sample_rate: f64, low_cutoff: f64, high_cutoff: f64, order: usize) -> Option<RealSignal<T>> {
+        Some(RealSignal::from_vec(sample_rate, fir_bpf(low_cutoff/sample_rate*2.0, high_cutoff/sample_rate*2.0, order)?))
+    }
+}
+
 #[test]
 fn test_fir() -> anyhow::Result<()> {
     let lpf = Signal::<f32>::from_vec(192000.0, fir_lpf::<f32>(0.5, 512).unwrap());
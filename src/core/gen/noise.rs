@@ -28,7 +28,7 @@ fn test_noise() -> anyhow::Result<()> {
     // fwd complex
     let mut chirp = Signal::from_vec(192000.0, chirp_complex::<f64>(4096, -1.0, 1.0));
     let noise = noise_complex(chirp.len(), -6.0, 192000.0)?;
-    chirp += noise;
+    chirp.add_assign_aligned(&noise)?;
     spectrogram("plot/test/test_noise/noisy_chirp_fwd_spect.png", chirp.clone(), 512, 256+128+64+32+16, true, Some(-120.0));
     plot_complex("plot/test/test_noise/noisy_chirp_fwd.png", "Chirp [-0.5, 0.5]", &chirp);
     let chirp = chirp.fft_fwd()?;
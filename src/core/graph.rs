@@ -0,0 +1,268 @@
+use std::{sync::Arc, thread::{self, JoinHandle}};
+
+use anyhow::anyhow;
+use log::trace;
+
+use crate::{core::{block::{farrow::FarrowResampler, filter::Filter, hilbert::HilbertFilter, mixer::{DownConverter, Mixer, UpConverter}, refragment::Refragmenter, resample::Resampler}, stream::{AudioStream, Discontinuity, StreamMonitor, Subscriber}}, prelude::*};
+
+/// Common interface of the streaming blocks so they can be chained and run by a `Graph`
+pub trait Block<T: SignalType>: Send {
+    /// Consume one input chunk and return the output that is ready, possibly none
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>>;
+    /// End-of-stream, emit whatever is buffered. Called once, the block is not used afterwards.
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(Vec::new())
+    }
+}
+
+impl<T: SignalType> Block<T> for Box<dyn Block<T>> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        (**self).process(input)
+    }
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        (**self).flush()
+    }
+}
+
+/// Stateless per-chunk block from a closure
+pub struct MapBlock<F>(pub F);
+impl<T: SignalType, F: FnMut(Signal<T>) -> Signal<T> + Send> Block<T> for MapBlock<F> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(vec![(self.0)(input)])
+    }
+}
+
+/// Blocks in sequence, itself a block
+pub struct Chain<T: SignalType> {
+    blocks: Vec<Box<dyn Block<T>>>,
+}
+impl<T: SignalType> Chain<T> {
+    pub fn new() -> Chain<T> {
+        Chain { blocks: Vec::new() }
+    }
+    pub fn then(mut self, block: impl Block<T> + 'static) -> Chain<T> {
+        self.blocks.push(Box::new(block));
+        self
+    }
+}
+impl<T: SignalType> Default for Chain<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T: SignalType> Block<T> for Chain<T> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        let mut chunks = vec![input];
+        for block in self.blocks.iter_mut() {
+            let mut next = Vec::new();
+            for chunk in chunks {
+                next.append(&mut block.process(chunk)?);
+            }
+            chunks = next;
+        }
+        Ok(chunks)
+    }
+    /// Each block's tail runs through the rest of the chain before that block is flushed
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        let mut out = Vec::new();
+        for idx in 0..self.blocks.len() {
+            let mut chunks = self.blocks[idx].flush()?;
+            for block in self.blocks[idx + 1..].iter_mut() {
+                let mut next = Vec::new();
+                for chunk in chunks {
+                    next.append(&mut block.process(chunk)?);
+                }
+                chunks = next;
+            }
+            out.append(&mut chunks);
+        }
+        Ok(out)
+    }
+}
+
+impl<T: SignalType> Block<T> for Filter<T> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(Filter::process(self, input).into_iter().collect())
+    }
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(Filter::flush(self).into_iter().collect())
+    }
+}
+impl<T: SignalType> Block<T> for Refragmenter<T> {
+    fn process(&mut self, mut input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        self.push(&mut input);
+        Ok(self.collect())
+    }
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(Refragmenter::flush(self).into_iter().collect())
+    }
+}
+impl<T: SignalType> Block<T> for Resampler<T> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(vec![Resampler::process(self, input)])
+    }
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(vec![Resampler::flush(self)])
+    }
+}
+impl<T: SignalType> Block<T> for FarrowResampler<T> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(vec![FarrowResampler::process(self, input)])
+    }
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(vec![FarrowResampler::flush(self)])
+    }
+}
+impl<T: SignalType> Block<T> for Mixer {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(vec![Mixer::process(self, input)])
+    }
+}
+impl<T: SignalType> Block<T> for HilbertFilter<T> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(HilbertFilter::process(self, input).into_iter().collect())
+    }
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(HilbertFilter::flush(self).into_iter().collect())
+    }
+}
+impl<T: SignalType> Block<T> for DownConverter<T> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(DownConverter::process(self, input).into_iter().collect())
+    }
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(DownConverter::flush(self).into_iter().collect())
+    }
+}
+impl<T: SignalType> Block<T> for UpConverter<T> {
+    fn process(&mut self, input: Signal<T>) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(UpConverter::process(self, input).into_iter().collect())
+    }
+    fn flush(&mut self) -> anyhow::Result<Vec<Signal<T>>> {
+        Ok(UpConverter::flush(self).into_iter().collect())
+    }
+}
+
+/// Runs blocks on worker threads between `AudioStream`s. Closing an input stream flushes the
+/// block and closes its output, so closing the source shuts the whole graph down in order.
+#[derive(Default)]
+pub struct Graph {
+    workers: Vec<JoinHandle<anyhow::Result<()>>>,
+}
+impl Graph {
+    pub fn new() -> Graph {
+        Graph::default()
+    }
+    /// Feed `input` through `block` into `output` on a new worker thread.
+    /// Subscribes immediately, so nothing sent to `input` after this call is missed.
+    pub fn connect<T: SignalType>(&mut self, input: &AudioStream<T>, block: impl Block<T> + 'static, output: Arc<AudioStream<T>>) {
+        let sub = input.get_subscriber();
+        self.workers.push(thread::spawn(move || run_block(sub, block, output)));
+    }
+    /// `connect` into a new output stream
+    pub fn add<T: SignalType>(&mut self, input: &AudioStream<T>, block: impl Block<T> + 'static) -> Arc<AudioStream<T>> {
+        let output = Arc::new(AudioStream::new());
+        self.connect(input, block, output.clone());
+        output
+    }
+    /// One worker per block, each with its own intermediate stream. Returns the last stream.
+    pub fn chain<T: SignalType>(&mut self, input: &AudioStream<T>, blocks: Vec<Box<dyn Block<T>>>) -> Arc<AudioStream<T>> {
+        let mut stream: Option<Arc<AudioStream<T>>> = None;
+        for block in blocks {
+            let next = self.add(stream.as_deref().unwrap_or(input), block);
+            stream = Some(next);
+        }
+        stream.unwrap_or_else(|| self.add(input, MapBlock(|x| x)))
+    }
+    /// Wait for every worker to finish, i.e. for their inputs to be closed and drained
+    pub fn join(self) -> anyhow::Result<()> {
+        for worker in self.workers {
+            worker.join().map_err(|_| anyhow!("mulink-dsp::graph_worker_panicked"))??;
+        }
+        Ok(())
+    }
+}
+
+/// Keep the time the block produced (filter delay compensation, resampler time mapping)
+fn send<T: SignalType>(output: &AudioStream<T>, out: Signal<T>) -> anyhow::Result<()> {
+    let time = out.time;
+    output.lock()?.send_at(out, time)
+}
+
+fn run_block<T: SignalType>(sub: Subscriber<Signal<T>>, mut block: impl Block<T>, output: Arc<AudioStream<T>>) -> anyhow::Result<()> {
+    let result = (|| {
+        let mut monitor = StreamMonitor::new();
+        for chunk in sub.iter() {
+            let mut chunk = Arc::unwrap_or_clone(chunk);
+            // Blocks see gaps this subscriber missed too. Resets are passed on downstream.
+            if monitor.annotate(&mut chunk) == Some(Discontinuity::Reset) {
+                output.lock()?.reset();
+            }
+            for out in block.process(chunk)? {
+                send(&output, out)?;
+            }
+        }
+        trace!("Graph worker reached end-of-stream");
+        for out in block.flush()? {
+            send(&output, out)?;
+        }
+        Ok(())
+    })();
+    // Propagate end-of-stream downstream even if the block failed
+    output.close()?;
+    result
+}
+
+#[test]
+fn test_graph() -> anyhow::Result<()> {
+    use crate::core::signal::FromFunction;
+    use itertools::Itertools;
+    use num::Complex;
+
+    let tone = |freq: f64| move |x: f64| Complex::new(f64::cos(freq * 2.0 * std::f64::consts::PI * x), f64::sin(freq * 2.0 * std::f64::consts::PI * x));
+    let sig = Signal::from_function(48000.0, 9600, tone(3000.0));
+
+    // Reference: the same blocks driven by hand
+    let mut filter = Filter::<f64>::lowpass(8000.0, 64, 48000.0)?;
+    let mut reference = Chain::new().then(Mixer::new(48000.0, -2000.0)).then(Refragmenter::<f64>::new(48000.0, 500));
+    let mut expected = Vec::new();
+    for chunk in sig.split_chunks(480) {
+        for out in Block::process(&mut filter, chunk)? {
+            expected.append(&mut reference.process(out)?);
+        }
+    }
+    for out in Block::flush(&mut filter)? {
+        expected.append(&mut reference.process(out)?);
+    }
+    expected.append(&mut reference.flush()?);
+
+    // Source -> filter -> (mixer -> refragmenter)
+    let source = AudioStream::<f64>::new();
+    let mut graph = Graph::new();
+    let filtered = graph.add(&source, Filter::<f64>::lowpass(8000.0, 64, 48000.0)?);
+    let filtered_sink = filtered.get_subscriber();
+    let out = graph.chain(&filtered, vec![Box::new(Mixer::new(48000.0, -2000.0)), Box::new(Refragmenter::<f64>::new(48000.0, 500))]);
+    let sink = out.get_subscriber();
+
+    for chunk in sig.split_chunks(480) {
+        source.lock()?.send(chunk)?;
+    }
+    source.close()?;
+    let received = sink.iter().collect_vec();
+    graph.join()?;
+    assert!(out.is_closed() && sink.is_finished());
+
+    assert_eq!(received.len(), expected.len());
+    assert!(received.iter().all(|x| x.len() == 500));
+    assert!(received.iter().zip(expected.iter()).all(|(a, b)| a.iter().zip(b.iter()).all(|(x, y)| (x - y).norm() < 1e-9)));
+    // Stream times are contiguous
+    assert!(received.iter().enumerate().all(|(idx, x)| x.time == 500 * idx as i64));
+    // The filter's delay compensation survives the stream: same times as driven by hand
+    let mut filter = Filter::<f64>::lowpass(8000.0, 64, 48000.0)?;
+    let mut by_hand = sig.split_chunks(480).into_iter().flat_map(|x| filter.process(x)).map(|x| x.time).collect_vec();
+    by_hand.extend(filter.flush().map(|x| x.time));
+    let filtered = filtered_sink.iter().map(|x| (x.time, x.stream.unwrap().discontinuity)).collect_vec();
+    assert!(by_hand[0] < 0);
+    assert_eq!(filtered, by_hand.into_iter().map(|x| (x, None)).collect_vec());
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, Result};
+use hound::SampleFormat;
+use itertools::Itertools;
+use num::Complex;
+
+use crate::{core::{signal::SignalMeta, stream::StreamInfo, tag::Tag}, io::wav::{read_wav_multichannel, write_wav_multichannel}, prelude::*};
+
+/// Channel-aligned container for array recordings. Every channel shares `sample_rate`, `time` and length.
+#[derive(Clone)]
+pub struct MultiSignal<T> {
+    pub sample_rate: f64,
+    pub time: i64,
+    pub meta: Option<SignalMeta>,
+    /// As `Signal::stream`, shared by all channels
+    pub stream: Option<StreamInfo>,
+    /// As `Signal::tags`, a tag marks the frame on every channel
+    pub tags: Vec<Tag>,
+    channels: Vec<Vec<Complex<T>>>,
+}
+
+impl<T: SignalType> MultiSignal<T> {
+    pub fn new(sample_rate: f64, n_channels: usize) -> MultiSignal<T> {
+        MultiSignal {
+            sample_rate,
+            time: 0,
+            meta: None,
+            stream: None,
+            tags: Vec::new(),
+            channels: vec![Vec::new(); n_channels],
+        }
+    }
+    /// All channels must share `sample_rate`, `time` and length. Stream info and tags come from the first channel.
+    pub fn from_channels(channels: Vec<Signal<T>>) -> Result<MultiSignal<T>> {
+        let Some(first) = channels.first() else {
+            return Err(anyhow!("MultiSignal requires at least one channel"));
+        };
+        let (sample_rate, time, len, meta) = (first.sample_rate, first.time, first.len(), first.meta.clone());
+        let (stream, tags) = (first.stream, first.tags.clone());
+        if let Some((idx, ch)) = channels.iter().find_position(|ch| ch.sample_rate != sample_rate || ch.time != time || ch.len() != len) {
+            return Err(anyhow!(
+                "Channel {idx} is not aligned: {}Hz@{}x{} vs {sample_rate}Hz@{time}x{len}",
+                ch.sample_rate, ch.time, ch.len()
+            ));
+        }
+        Ok(MultiSignal {
+            sample_rate,
+            time,
+            meta,
+            stream,
+            tags,
+            channels: channels.into_iter().map(|ch| ch.to_vec()).collect_vec(),
+        })
+    }
+    /// `samples` is frame-major: `[ch0, ch1, .., chN, ch0, ..]`
+    pub fn from_interleaved(sample_rate: f64, n_channels: usize, samples: &[Complex<T>]) -> Result<MultiSignal<T>> {
+        if n_channels == 0 || !samples.len().is_multiple_of(n_channels) {
+            return Err(anyhow!("{} samples cannot be split into {n_channels} channels", samples.len()));
+        }
+        let mut out = MultiSignal::new(sample_rate, n_channels);
+        samples.chunks_exact(n_channels).for_each(|frame| {
+            out.channels.iter_mut().zip(frame).for_each(|(ch, x)| ch.push(*x));
+        });
+        Ok(out)
+    }
+    pub fn interleaved(&self) -> Vec<Complex<T>> {
+        (0..self.len()).flat_map(|idx| self.channels.iter().map(move |ch| ch[idx])).collect_vec()
+    }
+    pub fn n_channels(&self) -> usize {
+        self.channels.len()
+    }
+    /// Samples per channel
+    pub fn len(&self) -> usize {
+        self.channels.first().map(|ch| ch.len()).unwrap_or(0)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Copy of a single channel, tagged with the shared `time`
+    pub fn channel(&self, idx: usize) -> Option<Signal<T>> {
+        let mut sig = Signal::from_vec(self.sample_rate, self.channels.get(idx)?.clone());
+        sig.time = self.time;
+        sig.meta = self.meta.clone();
+        sig.stream = self.stream;
+        sig.tags = self.tags.clone();
+        Some(sig)
+    }
+    pub fn channel_samples(&self, idx: usize) -> Option<&[Complex<T>]> {
+        self.channels.get(idx).map(|ch| ch.as_slice())
+    }
+    pub fn channel_samples_mut(&mut self, idx: usize) -> Option<&mut [Complex<T>]> {
+        self.channels.get_mut(idx).map(|ch| ch.as_mut_slice())
+    }
+    pub fn into_channels(self) -> Vec<Signal<T>> {
+        let (sample_rate, time, meta, stream, tags) = (self.sample_rate, self.time, self.meta, self.stream, self.tags);
+        self.channels.into_iter().map(|ch| {
+            let mut sig = Signal::from_vec(sample_rate, ch);
+            sig.time = time;
+            sig.meta = meta.clone();
+            sig.stream = stream;
+            sig.tags = tags.clone();
+            sig
+        }).collect_vec()
+    }
+    /// Apply `func` to every channel and re-assemble. Outputs must stay aligned.
+    pub fn map_channels(self, mut func: impl FnMut(usize, Signal<T>) -> Result<Signal<T>>) -> Result<MultiSignal<T>> {
+        let channels = self.into_channels().into_iter().enumerate().map(|(idx, ch)| func(idx, ch)).try_collect()?;
+        MultiSignal::from_channels(channels)
+    }
+    pub fn fft_fwd(self) -> Result<MultiSignal<T>> {
+        self.map_channels(|_, ch| ch.fft_fwd())
+    }
+    pub fn fft_rev(self) -> Result<MultiSignal<T>> {
+        self.map_channels(|_, ch| ch.fft_rev())
+    }
+    /// Append `other` to the end of every channel. Its tags move with its samples, so they keep
+    /// their times when `other` starts where `self` ends, as with `Signal::concat`. An empty
+    /// `self` adopts the time of `other`.
+    pub fn append(&mut self, other: &mut MultiSignal<T>) -> Result<()> {
+        if other.n_channels() != self.n_channels() {
+            return Err(anyhow!("Channel count mismatch: {} vs {}", self.n_channels(), other.n_channels()));
+        }
+        if other.sample_rate != self.sample_rate {
+            return Err(anyhow!("Sample rate mismatch: {} vs {}", self.sample_rate, other.sample_rate));
+        }
+        let len = other.len();
+        if let Some(idx) = other.channels.iter().position(|ch| ch.len() != len) {
+            return Err(anyhow!("Channel {idx} has {} samples, expected {len}", other.channels[idx].len()));
+        }
+        if self.is_empty() {
+            self.time = other.time;
+        }
+        let offset = self.time + self.len() as i64 - other.time;
+        self.tags.extend(other.tags.drain(..).map(|tag| Tag { time: tag.time + offset, ..tag }));
+        self.channels.iter_mut().zip(other.channels.iter_mut()).for_each(|(a, b)| a.append(b));
+        Ok(())
+    }
+    /// Remove the first `len` samples of every channel and return them, with their tags
+    pub fn drain_front(&mut self, len: usize) -> MultiSignal<T> {
+        let len = usize::min(len, self.len());
+        let count = self.tags.partition_point(|tag| tag.time < self.time + len as i64);
+        let out = MultiSignal {
+            sample_rate: self.sample_rate,
+            time: self.time,
+            meta: self.meta.clone(),
+            stream: self.stream.take(),
+            tags: self.tags.drain(..count).collect_vec(),
+            channels: self.channels.iter_mut().map(|ch| ch.drain(0..len).collect_vec()).collect_vec(),
+        };
+        self.time += len as i64;
+        out
+    }
+    pub fn write_wav(&self, path: &str, sample_format: SampleFormat, normalize: bool) -> Result<()> {
+        write_wav_multichannel(path, self, sample_format, normalize)
+    }
+    pub fn read_wav(path: &str) -> Result<MultiSignal<T>> {
+        read_wav_multichannel(path)
+    }
+}
+
+#[test]
+fn test_multi_signal() -> anyhow::Result<()> {
+    use crate::core::{block::{filter::MultiFilter, refragment::MultiRefragmenter}, signal::FromFunction, stream::Discontinuity, tag::TagValue};
+
+    let channels = (0..4).map(|ch| {
+        Signal::from_function(192000.0, 1000, |x| f64::sin((10000.0 + 1000.0 * ch as f64) * core::f64::consts::PI * 2.0 * x))
+    }).collect_vec();
+    let sig = MultiSignal::from_channels(channels.clone())?;
+    assert_eq!(sig.n_channels(), 4);
+    assert_eq!(sig.len(), 1000);
+
+    // Misaligned channels are rejected
+    let mut bad = channels.clone();
+    bad[2].time = 5;
+    assert!(MultiSignal::from_channels(bad).is_err());
+
+    // Interleave round trip
+    let restored = MultiSignal::from_interleaved(192000.0, 4, &sig.interleaved())?;
+    assert_eq!(restored.channel(3).unwrap().to_vec(), channels[3].to_vec());
+
+    // Refragment per channel, frames keep channel-aligned time
+    let mut refrag = MultiRefragmenter::new(192000.0, 4, 256);
+    refrag.push(&mut sig.clone())?;
+    let frags = (&mut refrag).collect_vec();
+    assert_eq!(frags.len(), 3);
+    assert_eq!(frags[2].time, 512);
+    assert!(frags.iter().all(|x| x.n_channels() == 4 && x.len() == 256));
+
+    // More than a fragment left at the end is padded to whole fragments
+    let mut refrag = MultiRefragmenter::<f64>::new(1000.0, 2, 10);
+    refrag.push(&mut MultiSignal::from_interleaved(1000.0, 2, &vec![Complex::new(1.0, 0.0); 50])?)?;
+    let tail = refrag.finish().unwrap();
+    assert_eq!((tail.time, tail.len()), (0, 30));
+    assert_eq!(tail.channel_samples(1).unwrap()[24..26], [Complex::new(1.0, 0.0), Complex::new(0.0, 0.0)]);
+
+    // Gaps are zero-filled, a reset drops the partial fragment, tags follow their frame
+    let frame = |len: usize, discontinuity: Option<Discontinuity>| -> anyhow::Result<MultiSignal<f64>> {
+        let mut sig = MultiSignal::from_interleaved(1000.0, 2, &vec![Complex::new(1.0, 0.0); 2 * len])?;
+        sig.stream = Some(StreamInfo { seq: 0, discontinuity });
+        Ok(sig)
+    };
+    let mut refrag = MultiRefragmenter::<f64>::new(1000.0, 2, 10);
+    refrag.push(&mut frame(4, None)?)?;
+    refrag.push(&mut frame(3, Some(Discontinuity::Reset))?)?;
+    let mut tagged = frame(8, Some(Discontinuity::Gap { samples: 5 }))?;
+    tagged.tags.push(Tag { time: tagged.time + 6, key: "detect".to_string(), value: TagValue::Flag });
+    refrag.push(&mut tagged)?;
+    let frags = (&mut refrag).collect_vec();
+    assert_eq!(frags.len(), 1);
+    assert_eq!(frags[0].channel_samples(0).unwrap().iter().map(|x| x.re).collect_vec(), [1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0]);
+    assert_eq!(refrag.pending(), 6);
+    let tail = refrag.finish().unwrap();
+    assert_eq!((tail.time, tail.len()), (10, 10));
+    assert_eq!(tail.tags.iter().map(|tag| tag.time).collect_vec(), [14]);
+
+    // Filter per channel matches single channel filtering
+    let kern = Signal::from_vec(192000.0, crate::core::r#gen::fir::fir_lpf::<f64>(0.25, 64).unwrap());
+    let filt = MultiFilter::<f64>::new(kern.clone(), 4)?;
+    let filtered = filt.process_and_finish(sig.clone()).unwrap();
+    let reference = crate::core::block::filter::Filter::<f64>::new(kern)?.process_and_finish(channels[1].clone()).unwrap();
+    assert_eq!(filtered.time, reference.time);
+    assert_eq!(filtered.channel(1).unwrap().to_vec(), reference.to_vec());
+
+    // Appending checks the sample rate and carries the tags over
+    let mut head = frame(4, None)?;
+    let mut next = frame(3, None)?;
+    next.time = 4;
+    next.tags.push(Tag { time: 5, key: "detect".to_string(), value: TagValue::Flag });
+    assert!(head.append(&mut MultiSignal::from_interleaved(2000.0, 2, &[Complex::new(1.0, 0.0); 6])?).is_err());
+    head.append(&mut next)?;
+    assert_eq!((head.time, head.len(), head.tags.iter().map(|tag| tag.time).collect_vec()), (0, 7, vec![5]));
+    let mut empty = MultiSignal::<f64>::new(1000.0, 2);
+    let mut late = frame(2, None)?;
+    late.time = 100;
+    empty.append(&mut late)?;
+    assert_eq!(empty.time, 100);
+
+    // Multichannel WAV round trip
+    let dir = std::env::temp_dir().join("mulink_test_multi_signal");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("array.wav");
+    sig.write_wav(path.to_str().unwrap(), SampleFormat::Float, false)?;
+    let read = MultiSignal::<f64>::read_wav(path.to_str().unwrap())?;
+    assert_eq!(read.n_channels(), 4);
+    assert_eq!(read.len(), 1000);
+    assert!((read.channel(2).unwrap()[10].re - sig.channel(2).unwrap()[10].re).abs() < 1e-6);
+    Ok(())
+}
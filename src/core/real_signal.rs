@@ -0,0 +1,133 @@
+use std::ops::{Deref, DerefMut};
+
+use anyhow::{anyhow, Result};
+use hound::SampleFormat;
+use itertools::Itertools;
+
+use crate::{core::{block::filter::{fftfilt_real, ConvShape}, signal::SignalMeta}, io::wav::{read_wav_real, write_wav_real}, prelude::*};
+
+/// Real-valued counterpart of `Signal<T>` with the same `sample_rate`/`time` semantics.
+/// Use for passband recordings where the imaginary part would always be zero.
+#[derive(Clone)]
+pub struct RealSignal<T> {
+    pub sample_rate: f64,
+    pub time: i64,
+    pub meta: Option<SignalMeta>,
+    samples: Vec<T>,
+}
+
+impl<T: SignalType> RealSignal<T> {
+    pub fn new(sample_rate: f64) -> RealSignal<T> {
+        RealSignal {
+            time: 0,
+            sample_rate,
+            meta: None,
+            samples: Vec::new(),
+        }
+    }
+    pub fn from_vec(sample_rate: f64, samples: Vec<T>) -> RealSignal<T> {
+        RealSignal {
+            time: 0,
+            sample_rate,
+            meta: None,
+            samples,
+        }
+    }
+    pub fn from_function(sample_rate: f64, len: usize, func: impl Fn(f64) -> T) -> RealSignal<T> {
+        RealSignal {
+            time: 0,
+            sample_rate,
+            meta: None,
+            samples: (0..len).map(|x| func((x as f64) / sample_rate)).collect_vec(),
+        }
+    }
+    /// Lossless conversion to the complex form (imaginary parts are zero)
+    pub fn to_complex(&self) -> Signal<T> {
+        let mut out = Signal::from_vec(self.sample_rate, self.samples.clone());
+        out.time = self.time;
+        out.meta = self.meta.clone();
+        out
+    }
+    pub fn into_vec(self) -> Vec<T> {
+        self.samples
+    }
+    pub fn write_wav(&self, path: &str, sample_format: SampleFormat, normalize: bool) -> Result<()> {
+        write_wav_real(path, self, sample_format, normalize)
+    }
+    pub fn read_wav(path: &str) -> Result<RealSignal<T>> {
+        read_wav_real(path)
+    }
+    /// Convolve with a real kernel through half-length FFTs, see `fftfilt`
+    pub fn fftfilt(&self, filter: &RealSignal<T>, shape: ConvShape) -> Result<RealSignal<T>> {
+        fftfilt_real(self, filter, shape)
+    }
+}
+
+impl<T: SignalType> Signal<T> {
+    /// Keep only the real part. Use `RealSignal::try_from` when the imaginary part must be zero.
+    pub fn to_real(&self) -> RealSignal<T> {
+        let mut out = RealSignal::from_vec(self.sample_rate, self.re());
+        out.time = self.time;
+        out.meta = self.meta.clone();
+        out
+    }
+}
+
+impl<T: SignalType> From<RealSignal<T>> for Signal<T> {
+    fn from(value: RealSignal<T>) -> Self {
+        value.to_complex()
+    }
+}
+
+impl<T: SignalType> TryFrom<Signal<T>> for RealSignal<T> {
+    type Error = anyhow::Error;
+
+    /// Fails if any sample has a non-zero imaginary part
+    fn try_from(value: Signal<T>) -> Result<Self> {
+        if let Some((idx, x)) = value.iter().find_position(|x| !x.im.is_zero()) {
+            return Err(anyhow!("Signal is not real: sample {idx} has imaginary part {:?}", x.im));
+        }
+        Ok(value.to_real())
+    }
+}
+
+impl<T> Deref for RealSignal<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.samples
+    }
+}
+impl<T> DerefMut for RealSignal<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.samples
+    }
+}
+
+#[test]
+fn test_real_signal() -> anyhow::Result<()> {
+    let mut sig = RealSignal::from_function(192000.0, 1024, |x| f64::sin(10000.0 * core::f64::consts::PI * 2.0 * x));
+    sig.time = 512;
+
+    // Round trip through the complex form is lossless
+    let complex: Signal<f64> = sig.clone().into();
+    assert_eq!(complex.time, 512);
+    let restored = RealSignal::try_from(complex.clone())?;
+    assert_eq!(restored.time, 512);
+    assert!(restored.iter().zip(sig.iter()).all(|(a, b)| a == b));
+
+    // A signal with an imaginary component is rejected
+    let mut complex = complex;
+    complex[3] = num::Complex::new(0.0, 1.0);
+    assert!(RealSignal::try_from(complex).is_err());
+
+    // Real filtering agrees with the complex path
+    let kern = RealSignal::<f64>::fir_lpf(192000.0, 48000.0, 64).unwrap();
+    let filtered = sig.fftfilt(&kern, ConvShape::FULL)?;
+    assert_eq!(filtered.len(), sig.len() + kern.len() - 1);
+    let reference = sig.to_complex().fftfilt(&kern.to_complex(), ConvShape::SAME)?;
+    let filtered = sig.fftfilt(&kern, ConvShape::SAME)?;
+    assert_eq!((filtered.time, filtered.len()), (reference.time, reference.len()));
+    assert!(filtered.iter().zip(reference.iter()).all(|(a, b)| (a - b.re).abs() < 1e-12));
+    Ok(())
+}
@@ -0,0 +1,326 @@
+use std::{sync::Arc, thread::{self, JoinHandle}, time::Duration};
+
+use num::{Complex, Zero};
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::{core::{signal::SignalMeta, stream::{AudioStream, Discontinuity, StreamMonitor, Subscriber}}, prelude::*};
+
+// Real-time ingestion. An audio callback must not lock, allocate or wait, so it writes into a
+// pre-allocated SPSC ring (`RtProducer`) and an `RtPump` on an ordinary thread moves the samples
+// into an `AudioStream` in `chunk_len` chunks. Samples that do not fit are dropped and show up
+// as a `Gap` at the right stream time. Playback is the mirror image: `RtFeeder` fills the ring
+// from a subscriber and the callback drains it through `RtConsumer`.
+
+/// Lost samples, positioned by the number of samples written to the ring before the loss
+#[derive(Clone, Copy, Debug)]
+struct Loss {
+    at: u64,
+    samples: u64,
+}
+
+/// Losses not yet reported that do not fit in the event ring are merged into the next one
+const LOSS_EVENTS: usize = 64;
+
+/// Real-time side of `rt_ring`, owned by the audio callback. Never blocks or allocates.
+pub struct RtProducer<T: SignalType> {
+    samples: Producer<Complex<T>>,
+    losses: Producer<Loss>,
+    written: u64,
+    /// Samples lost since the last `Loss` that made it into the event ring
+    unreported: u64,
+    dropped: u64,
+}
+impl<T: SignalType> RtProducer<T> {
+    /// Write as many samples as fit, the rest are dropped. Returns the number written.
+    pub fn push(&mut self, samples: &[Complex<T>]) -> usize {
+        self.push_iter(samples.iter().copied())
+    }
+    /// `push` from an iterator, e.g. one converting the device's sample format on the fly
+    pub fn push_iter<I: IntoIterator<Item = Complex<T>>>(&mut self, samples: I) -> usize where I::IntoIter: ExactSizeIterator {
+        let samples = samples.into_iter();
+        let len = samples.len();
+        // A loss has to be in the event ring before any sample that comes after it
+        if self.unreported > 0 {
+            if self.losses.push(Loss { at: self.written, samples: self.unreported }).is_err() {
+                self.lose(len as u64);
+                return 0;
+            }
+            self.unreported = 0;
+        }
+        let n = usize::min(len, self.samples.slots());
+        let written = match self.samples.write_chunk_uninit(n) {
+            Ok(chunk) => chunk.fill_from_iter(samples),
+            Err(_) => 0,
+        };
+        self.written += written as u64;
+        self.lose((len - written) as u64);
+        written
+    }
+    fn lose(&mut self, samples: u64) {
+        self.unreported += samples;
+        self.dropped += samples;
+    }
+    /// Samples dropped because the ring was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+    /// Free space in samples
+    pub fn available(&self) -> usize {
+        self.samples.slots()
+    }
+}
+
+/// Non-real-time side of `rt_ring`, sends what the producer wrote into the `AudioStream`
+pub struct RtPump<T: SignalType> {
+    samples: Consumer<Complex<T>>,
+    losses: Consumer<Loss>,
+    stream: Arc<AudioStream<T>>,
+    sample_rate: f64,
+    chunk_len: usize,
+    read: u64,
+    /// Partial chunk, sent once `chunk_len` samples are collected or before a gap
+    pending: Vec<Complex<T>>,
+    meta: Option<SignalMeta>,
+}
+impl<T: SignalType> RtPump<T> {
+    /// Attach `meta` to every chunk, e.g. the wall-clock `epoch` of the first sample
+    pub fn with_meta(mut self, meta: SignalMeta) -> RtPump<T> {
+        self.meta = Some(meta);
+        self
+    }
+    /// Move everything the producer has written so far into the stream. Only full chunks are
+    /// sent, except right before a gap. Returns the number of samples taken from the ring.
+    pub fn pump(&mut self) -> anyhow::Result<usize> {
+        let start = self.read;
+        loop {
+            // Samples before the loss check: a loss pushed after this point comes after all of them
+            let available = self.samples.slots();
+            let loss = self.losses.peek().ok().copied();
+            // Read up to the next loss, or everything available
+            let limit = loss.map_or(u64::MAX, |loss| loss.at.saturating_sub(self.read));
+            let n = usize::min(available, usize::try_from(limit).unwrap_or(usize::MAX));
+            self.take(n)?;
+            match loss {
+                Some(loss) if loss.at <= self.read => {
+                    let _ = self.losses.pop();
+                    self.flush()?;
+                    self.stream.lock()?.overrun(loss.samples as usize);
+                }
+                _ => break,
+            }
+        }
+        Ok((self.read - start) as usize)
+    }
+    fn take(&mut self, mut n: usize) -> anyhow::Result<()> {
+        while n > 0 {
+            // At most up to the end of the current chunk
+            let len = usize::min(n, self.chunk_len - self.pending.len());
+            let chunk = self.samples.read_chunk(len)?;
+            let (first, second) = chunk.as_slices();
+            self.pending.extend_from_slice(first);
+            self.pending.extend_from_slice(second);
+            chunk.commit_all();
+            self.read += len as u64;
+            n -= len;
+            if self.pending.len() == self.chunk_len {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+    /// Send the partial chunk now
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if !self.pending.is_empty() {
+            let samples = std::mem::replace(&mut self.pending, Vec::with_capacity(self.chunk_len));
+            let mut sig = Signal::from_vec(self.sample_rate, samples);
+            sig.meta = self.meta.clone();
+            self.stream.lock()?.send(sig)?;
+        }
+        Ok(())
+    }
+    /// `true` once the producer was dropped and everything it wrote was pumped
+    pub fn is_finished(&self) -> bool {
+        self.samples.is_abandoned() && self.samples.is_empty() && self.losses.is_empty()
+    }
+    /// Pump every `interval` on a new thread. When the producer is dropped the partial chunk is
+    /// sent and the stream closed.
+    pub fn spawn(mut self, interval: Duration) -> JoinHandle<anyhow::Result<()>> {
+        thread::spawn(move || {
+            let result = (|| {
+                while !self.is_finished() {
+                    if self.pump()? == 0 {
+                        thread::sleep(interval);
+                    }
+                }
+                self.flush()
+            })();
+            self.stream.close()?;
+            result
+        })
+    }
+}
+
+/// Real-time ingestion path into `stream`. `capacity` samples are allocated up front and should
+/// cover a few callback periods plus the pump interval.
+pub fn rt_ring<T: SignalType>(stream: Arc<AudioStream<T>>, sample_rate: f64, capacity: usize, chunk_len: usize) -> (RtProducer<T>, RtPump<T>) {
+    let (samples_tx, samples_rx) = RingBuffer::new(capacity);
+    let (losses_tx, losses_rx) = RingBuffer::new(LOSS_EVENTS);
+    let chunk_len = chunk_len.max(1);
+    (
+        RtProducer { samples: samples_tx, losses: losses_tx, written: 0, unreported: 0, dropped: 0 },
+        RtPump { samples: samples_rx, losses: losses_rx, stream, sample_rate, chunk_len, read: 0, pending: Vec::with_capacity(chunk_len), meta: None },
+    )
+}
+
+/// Real-time side of `rt_playback`, owned by the audio callback. Never blocks or allocates.
+pub struct RtConsumer<T: SignalType> {
+    samples: Consumer<Complex<T>>,
+    underruns: u64,
+}
+impl<T: SignalType> RtConsumer<T> {
+    /// Next sample, silence if the feeder fell behind
+    pub fn pop(&mut self) -> Complex<T> {
+        self.samples.pop().unwrap_or_else(|_| {
+            self.underruns += 1;
+            Complex::zero()
+        })
+    }
+    /// Silent samples played because the ring was empty, including before the first chunk
+    pub fn underruns(&self) -> u64 {
+        self.underruns
+    }
+    /// Samples waiting to be played
+    pub fn available(&self) -> usize {
+        self.samples.slots()
+    }
+}
+
+/// Non-real-time side of `rt_playback`, copies the subscribed stream into the ring
+pub struct RtFeeder<T: SignalType> {
+    sub: Subscriber<Signal<T>>,
+    samples: Producer<Complex<T>>,
+}
+impl<T: SignalType> RtFeeder<T> {
+    /// Feed on a new thread, polling every `interval` while the ring is full. Gaps in the stream
+    /// are played as silence. The thread ends once the stream is closed and the ring has been
+    /// played out, or when the consumer is dropped.
+    pub fn spawn(mut self, interval: Duration) -> JoinHandle<anyhow::Result<()>> {
+        thread::spawn(move || {
+            let mut monitor = StreamMonitor::new();
+            for chunk in self.sub.iter() {
+                let silence = match monitor.check(&chunk) {
+                    Some(Discontinuity::Gap { samples }) => samples.max(0) as usize,
+                    _ => 0,
+                };
+                let mut samples = std::iter::repeat_n(Complex::zero(), silence).chain(chunk.iter().copied()).peekable();
+                while samples.peek().is_some() {
+                    if self.samples.is_abandoned() {
+                        return Ok(());
+                    }
+                    let n = self.samples.slots();
+                    if n == 0 {
+                        thread::sleep(interval);
+                        continue;
+                    }
+                    self.samples.write_chunk_uninit(n)?.fill_from_iter(&mut samples);
+                }
+            }
+            while !self.samples.is_abandoned() && self.samples.slots() < self.samples.buffer().capacity() {
+                thread::sleep(interval);
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Real-time playback path out of `stream`, subscribes straight away. `capacity` samples are
+/// allocated up front and set the playback latency.
+pub fn rt_playback<T: SignalType>(stream: &AudioStream<T>, capacity: usize) -> (RtFeeder<T>, RtConsumer<T>) {
+    let (samples_tx, samples_rx) = RingBuffer::new(capacity);
+    (RtFeeder { sub: stream.get_subscriber(), samples: samples_tx }, RtConsumer { samples: samples_rx, underruns: 0 })
+}
+
+#[test]
+fn test_rt_ring() -> anyhow::Result<()> {
+    use crate::core::stream::{Discontinuity, StreamMonitor};
+
+    let stream = Arc::new(AudioStream::<f32>::new());
+    let sub = stream.get_subscriber();
+    let (mut producer, mut pump) = rt_ring(stream.clone(), 8000.0, 100, 32);
+    let ramp = |start: usize, len: usize| (start..start + len).map(|x| Complex::new(x as f32, 0.0)).collect::<Vec<_>>();
+
+    // 80 samples fit, 20 of the next 40 are dropped
+    assert_eq!(producer.push(&ramp(0, 80)), 80);
+    assert_eq!(producer.push_iter(ramp(80, 40)), 20);
+    assert_eq!((producer.dropped(), producer.available()), (20, 0));
+    assert_eq!(pump.pump()?, 100);
+    // The next write goes after the gap
+    assert_eq!(producer.push(&ramp(120, 30)), 30);
+    assert_eq!(pump.pump()?, 30);
+    drop(producer);
+    assert!(pump.is_finished());
+    pump.flush()?;
+    stream.close()?;
+
+    let received = sub.iter().collect::<Vec<_>>();
+    let mut monitor = StreamMonitor::new();
+    let chunks = received.iter().map(|x| (x.time, x.len(), monitor.check(x))).collect::<Vec<_>>();
+    assert_eq!(chunks, vec![(0, 32, None), (32, 32, None), (64, 32, None), (96, 4, None), (120, 30, Some(Discontinuity::Gap { samples: 20 }))]);
+    // Every sample sits at its own stream time
+    assert!(received.iter().all(|x| x.iter().enumerate().all(|(idx, s)| s.re == (x.time + idx as i64) as f32)));
+
+    // Producer on another thread, pump thread closes the stream once the producer is gone
+    let stream = Arc::new(AudioStream::<f32>::new());
+    let sub = stream.get_subscriber();
+    let (mut producer, pump) = rt_ring(stream.clone(), 8000.0, 4096, 256);
+    let pump = pump.spawn(Duration::from_millis(1));
+    let callback = thread::spawn(move || {
+        for period in 0..50 {
+            while producer.available() < 64 {
+                thread::sleep(Duration::from_micros(100));
+            }
+            producer.push(&ramp(period * 64, 64));
+        }
+        producer.dropped()
+    });
+    assert_eq!(callback.join().unwrap(), 0);
+    let samples = sub.iter().flat_map(|x| x.to_vec()).map(|x| x.re).collect::<Vec<_>>();
+    pump.join().unwrap()?;
+    assert!(stream.is_closed());
+    assert_eq!(samples, (0..3200).map(|x| x as f32).collect::<Vec<_>>());
+
+    // Overruns from another thread while the pump runs, every gap lands at the right stream time
+    let stream = Arc::new(AudioStream::<f32>::new());
+    let sub = stream.get_subscriber();
+    let (mut producer, pump) = rt_ring(stream.clone(), 8000.0, 256, 64);
+    let pump = pump.spawn(Duration::from_micros(50));
+    let callback = thread::spawn(move || {
+        for period in 0..2000 {
+            producer.push(&ramp(period * 48, 48));
+            if period % 8 == 0 {
+                thread::sleep(Duration::from_micros(100));
+            }
+        }
+        producer.dropped()
+    });
+    let dropped = callback.join().unwrap();
+    let received = sub.iter().collect::<Vec<_>>();
+    pump.join().unwrap()?;
+    let mut monitor = StreamMonitor::new();
+    let mut gaps = 0;
+    for chunk in &received {
+        match monitor.check(chunk) {
+            Some(Discontinuity::Gap { samples }) => gaps += samples as u64,
+            None => (),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(chunk.iter().enumerate().all(|(idx, s)| s.re == (chunk.time + idx as i64) as f32));
+    }
+    let last = received.last().unwrap();
+    let sent = received.iter().map(|x| x.len() as u64).sum::<u64>();
+    assert_eq!(sent + dropped, 2000 * 48);
+    // Only a trailing loss with nothing written after it goes unreported
+    assert!(dropped > 0 && gaps <= dropped);
+    assert_eq!(last.time as u64 + last.len() as u64, sent + gaps);
+    Ok(())
+}
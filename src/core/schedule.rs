@@ -0,0 +1,207 @@
+use anyhow::anyhow;
+use num::{Complex, Zero};
+
+use crate::{core::tag::TagValue, prelude::*};
+
+/// Tag key marking the first emitted sample of a scheduled signal, the value is its `TxId`
+pub const TX_TAG: &str = "tx";
+
+/// What `TxScheduler::schedule` does with a signal whose target time was already rendered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Late {
+    /// Refuse it, timing is all that matters (ranging)
+    Reject,
+    /// Emit it whole from the next rendered sample, the report carries the actual time
+    Asap,
+    /// Keep the timing and drop the samples that are already past
+    Trim,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TxId(pub u64);
+
+/// Where a scheduled signal ended up in the output stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Emitted {
+    pub id: TxId,
+    /// Target `time` of the scheduled signal
+    pub requested: i64,
+    /// Stream time of its first emitted sample
+    pub time: i64,
+    /// Samples dropped under `Late::Trim`
+    pub trimmed: usize,
+}
+
+struct Pending<T: SignalType> {
+    id: TxId,
+    requested: i64,
+    sig: Signal<T>,
+    trimmed: usize,
+    started: bool,
+}
+
+/// Mixes signals into an outgoing sample stream exactly at their `time`, silence in between.
+/// Output time counts samples from `start`, on the same axis as the receive stream when the
+/// input and output share a clock, so `rx time + delay` schedules a reply `delay` samples later.
+pub struct TxScheduler<T: SignalType> {
+    sample_rate: f64,
+    /// Stream time of the next rendered sample
+    time: i64,
+    late: Late,
+    /// Sorted by time
+    queue: Vec<Pending<T>>,
+    next_id: u64,
+}
+
+impl<T: SignalType> TxScheduler<T> {
+    pub fn new(sample_rate: f64, start: i64) -> TxScheduler<T> {
+        TxScheduler { sample_rate, time: start, late: Late::Reject, queue: Vec::new(), next_id: 0 }
+    }
+    pub fn with_late(mut self, late: Late) -> Self {
+        self.late = late;
+        self
+    }
+    /// Stream time of the next sample `render` produces. Anything scheduled before it is late.
+    pub fn time(&self) -> i64 {
+        self.time
+    }
+    /// Signals waiting to be (completely) emitted
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+    /// Queue `sig` for emission at `sig.time`. Overlapping signals are summed.
+    pub fn schedule(&mut self, mut sig: Signal<T>) -> anyhow::Result<TxId> {
+        if sig.sample_rate != self.sample_rate {
+            return Err(anyhow!("Sample rate mismatch: {} vs {}", sig.sample_rate, self.sample_rate));
+        }
+        let requested = sig.time;
+        let mut trimmed = 0;
+        if requested < self.time {
+            match self.late {
+                Late::Reject => return Err(anyhow!("mulink-dsp::tx_schedule_late: {requested} is before {}", self.time)),
+                Late::Asap => sig.retime(self.time),
+                Late::Trim => {
+                    trimmed = usize::min((self.time - requested) as usize, sig.len());
+                    sig.trim(trimmed, 0);
+                    // Lands on the next rendered sample, even when nothing is left
+                    sig.time = self.time;
+                }
+            }
+        }
+        let id = TxId(self.next_id);
+        self.next_id += 1;
+        let pos = self.queue.partition_point(|x| x.sig.time <= sig.time);
+        self.queue.insert(pos, Pending { id, requested, sig, trimmed, started: false });
+        Ok(id)
+    }
+    /// `schedule` at stream time `time`
+    pub fn schedule_at(&mut self, mut sig: Signal<T>, time: i64) -> anyhow::Result<TxId> {
+        sig.retime(time);
+        self.schedule(sig)
+    }
+    /// Drop a signal that has not started yet, `false` if it is already (partly) emitted or unknown
+    pub fn cancel(&mut self, id: TxId) -> bool {
+        let before = self.queue.len();
+        self.queue.retain(|x| x.id != id || x.started);
+        self.queue.len() != before
+    }
+    /// The next `len` output samples. Each signal that starts in them is reported and tagged
+    /// `TX_TAG` at its first emitted sample. Tags of the scheduled signals are carried over.
+    pub fn render(&mut self, len: usize) -> (Signal<T>, Vec<Emitted>) {
+        let mut out = Signal::from_vec(self.sample_rate, vec![Complex::<T>::zero(); len]);
+        out.time = self.time;
+        let end = out.end_time();
+        let mut emitted = Vec::new();
+        for pending in self.queue.iter_mut().take_while(|x| x.sig.time < end) {
+            let part = pending.sig.slice_time(out.time, end);
+            let offset = (part.time - out.time) as usize;
+            out[offset..offset + part.len()].iter_mut().zip(part.iter()).for_each(|(a, b)| *a += b);
+            out.tags.extend(part.tags.iter().cloned());
+            if !pending.started && !pending.sig.is_empty() {
+                pending.started = true;
+                out.add_tag(offset, TX_TAG, TagValue::Int(pending.id.0 as i64));
+                emitted.push(Emitted { id: pending.id, requested: pending.requested, time: pending.sig.time, trimmed: pending.trimmed });
+            }
+        }
+        // Fully trimmed signals are reported when their slot comes up
+        for pending in self.queue.iter().filter(|x| x.sig.is_empty() && x.sig.time < end) {
+            emitted.push(Emitted { id: pending.id, requested: pending.requested, time: pending.sig.time, trimmed: pending.trimmed });
+        }
+        out.tags.sort_by_key(|tag| tag.time);
+        self.queue.retain(|x| x.sig.time >= end || (x.sig.end_time() > end && !x.sig.is_empty()));
+        self.time = end;
+        (out, emitted)
+    }
+}
+
+#[test]
+fn test_schedule() -> anyhow::Result<()> {
+    use crate::core::{signal::FromFunction, stream::AudioStream};
+
+    let ping = |value: f64, len: usize| Signal::from_vec(8000.0, vec![Complex::new(value, 0.0); len]);
+    let mut tx = TxScheduler::<f64>::new(8000.0, 1000);
+
+    // Two overlapping pings and one crossing chunk boundaries
+    let a = tx.schedule_at(ping(1.0, 100), 1100)?;
+    let b = tx.schedule_at(ping(2.0, 100), 1150)?;
+    let c = tx.schedule_at(ping(4.0, 300), 1400)?;
+    let cancelled = tx.schedule_at(ping(8.0, 10), 1900)?;
+    assert!(tx.cancel(cancelled) && !tx.cancel(cancelled));
+    assert!(tx.schedule_at(Signal::from_function(48000.0, 10, |_| 0.0), 2000).is_err());
+
+    let mut chunks = Vec::new();
+    let mut reports = Vec::new();
+    for _ in 0..4 {
+        let (chunk, mut emitted) = tx.render(256);
+        reports.append(&mut emitted);
+        chunks.push(chunk);
+    }
+    let out = Signal::concat_all(&chunks)?;
+    assert_eq!((out.time, out.len(), tx.time(), tx.pending()), (1000, 1024, 2024, 0));
+    let expected = (1000..2024).map(|t| {
+        let mut x = 0.0;
+        if (1100..1200).contains(&t) { x += 1.0 }
+        if (1150..1250).contains(&t) { x += 2.0 }
+        if (1400..1700).contains(&t) { x += 4.0 }
+        x
+    }).collect::<Vec<_>>();
+    assert_eq!(out.iter().map(|x| x.re).collect::<Vec<_>>(), expected);
+    assert_eq!(reports, vec![
+        Emitted { id: a, requested: 1100, time: 1100, trimmed: 0 },
+        Emitted { id: b, requested: 1150, time: 1150, trimmed: 0 },
+        Emitted { id: c, requested: 1400, time: 1400, trimmed: 0 },
+    ]);
+    // The tags travel with the samples
+    let tags = out.find_tags(TX_TAG).map(|x| (x.time, x.value.clone())).collect::<Vec<_>>();
+    assert_eq!(tags, vec![(1100, TagValue::Int(0)), (1150, TagValue::Int(1)), (1400, TagValue::Int(2))]);
+
+    // Late signals, per policy
+    assert!(tx.schedule_at(ping(1.0, 50), 2000).is_err());
+    let mut asap = TxScheduler::<f64>::new(8000.0, 0).with_late(Late::Asap);
+    asap.render(100);
+    let id = asap.schedule_at(ping(1.0, 50), 80)?;
+    let (out, emitted) = asap.render(100);
+    assert_eq!(emitted, vec![Emitted { id, requested: 80, time: 100, trimmed: 0 }]);
+    assert_eq!(out.iter().filter(|x| x.re == 1.0).count(), 50);
+    let mut trim = TxScheduler::<f64>::new(8000.0, 0).with_late(Late::Trim);
+    trim.render(100);
+    let id = trim.schedule_at(ping(1.0, 50), 80)?;
+    let gone = trim.schedule_at(ping(1.0, 10), 20)?;
+    let (out, emitted) = trim.render(100);
+    assert_eq!(emitted, vec![Emitted { id, requested: 80, time: 100, trimmed: 20 }, Emitted { id: gone, requested: 20, time: 100, trimmed: 10 }]);
+    assert_eq!(out.iter().take_while(|x| x.re == 1.0).count(), 30);
+    assert_eq!(trim.pending(), 0);
+
+    // Through an AudioStream the emitted time is the stream time of the tagged sample
+    let stream = AudioStream::<f64>::new();
+    let sub = stream.get_subscriber();
+    let mut tx = TxScheduler::<f64>::new(8000.0, stream.time());
+    let id = tx.schedule_at(ping(1.0, 10), 333)?;
+    for _ in 0..2 {
+        stream.lock()?.send(tx.render(256).0)?;
+    }
+    stream.close()?;
+    let tagged = sub.iter().flat_map(|x| x.tags.clone()).collect::<Vec<_>>();
+    assert_eq!((tagged[0].time, &tagged[0].value), (333, &TagValue::Int(id.0 as i64)));
+    Ok(())
+}
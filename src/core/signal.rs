@@ -1,12 +1,13 @@
 use std::ops::{AddAssign, Deref, DerefMut, DivAssign, MulAssign, RemAssign, SubAssign};
 
 use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
 use hound::SampleFormat;
 use itertools::Itertools;
 use num::{cast::AsPrimitive, Complex, FromPrimitive, Signed};
 use rand::distr::uniform::{SampleBorrow, SampleUniform};
 
-use crate::io::wav::{read_wav_complex, write_wav_complex};
+use crate::{core::{stream::StreamInfo, tag::Tag}, io::wav::{read_wav_complex, write_wav_complex}};
 
 #[derive(Clone,Copy,Debug)]
 pub enum Amplitude {
@@ -20,6 +21,26 @@ impl Amplitude {
             Amplitude::Log(a) => 10.0_f64.powf(*a/20.0),
         }).expect("Number conversion failed")
     }
+    /// 20*log10 of the linear amplitude
+    pub fn db(&self) -> f64 {
+        match self {
+            Amplitude::Linear(a) => 20.0 * a.log10(),
+            Amplitude::Log(a) => *a,
+        }
+    }
+    pub fn to_log(&self) -> Amplitude {
+        Amplitude::Log(self.db())
+    }
+    pub fn to_linear(&self) -> Amplitude {
+        Amplitude::Linear(self.linear())
+    }
+    /// Power quantities are stored as their root, so `db()` yields 10*log10(power)
+    pub fn from_power(power: f64) -> Amplitude {
+        Amplitude::Linear(power.sqrt())
+    }
+    pub fn power(&self) -> f64 {
+        self.linear::<f64>().powi(2)
+    }
 }
 
 pub trait SignalType: num::Float + std::fmt::Debug +  FromPrimitive + Signed + Send + Sync + AsPrimitive<i32> + AsPrimitive<f64> + AsPrimitive<f32> + SampleBorrow<Self> + SampleUniform + Default + AddAssign + SubAssign + DivAssign + MulAssign + RemAssign + 'static {}
@@ -35,6 +56,9 @@ impl<T: SignalType> FromVec<Complex<T>, T> for Signal<T> {
         Signal {
             time: 0,
             sample_rate,
+            meta: None,
+            stream: None,
+            tags: Vec::new(),
             samples,
         }
     }
@@ -44,6 +68,9 @@ impl<T: SignalType> FromVec<T, T> for Signal<T> {
         Signal {
             time: 0,
             sample_rate,
+            meta: None,
+            stream: None,
+            tags: Vec::new(),
             samples: samples
                 .iter()
                 .map(|x| Complex::new(*x, T::zero()))
@@ -60,6 +87,9 @@ impl<T: SignalType> FromFunction<Complex<T>, T> for Signal<T> {
         Signal {
             time: 0,
             sample_rate,
+            meta: None,
+            stream: None,
+            tags: Vec::new(),
             samples: (0..len).into_iter().map(|x| func((x as f64)/sample_rate)).collect_vec(),
         }
     }
@@ -71,10 +101,82 @@ impl<T: SignalType> FromFunction<T, T> for Signal<T> {
 }
 
 
+/// Physical quantity the samples represent
+#[derive(Clone, Debug, PartialEq)]
+pub enum Unit {
+    Counts,
+    Volts,
+    Pascals,
+    Other(String),
+}
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Counts => write!(f, "counts"),
+            Unit::Volts => write!(f, "V"),
+            Unit::Pascals => write!(f, "Pa"),
+            Unit::Other(unit) => write!(f, "{unit}"),
+        }
+    }
+}
+impl std::str::FromStr for Unit {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "counts" => Unit::Counts,
+            "V" => Unit::Volts,
+            "Pa" => Unit::Pascals,
+            other => Unit::Other(other.to_string()),
+        })
+    }
+}
+
+/// Optional description of where a `Signal` came from
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignalMeta {
+    /// Wall-clock time of stream sample index 0, see `Signal::start_time`
+    pub epoch: Option<DateTime<Utc>>,
+    /// RF/acoustic frequency that was mixed to 0 Hz, for baseband data
+    pub center_frequency: Option<f64>,
+    pub unit: Unit,
+    /// Physical units per sample value
+    pub gain: f64,
+}
+impl Default for SignalMeta {
+    fn default() -> Self {
+        SignalMeta {
+            epoch: None,
+            center_frequency: None,
+            unit: Unit::Counts,
+            gain: 1.0,
+        }
+    }
+}
+impl SignalMeta {
+    /// Short description for plot titles
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(epoch) = self.epoch {
+            parts.push(epoch.format("%Y-%m-%d %H:%M:%S%.3f UTC").to_string());
+        }
+        if let Some(fc) = self.center_frequency {
+            parts.push(format!("fc={fc} Hz"));
+        }
+        parts.push(if self.gain != 1.0 { format!("{} {}", self.gain, self.unit) } else { self.unit.to_string() });
+        parts.join(", ")
+    }
+}
+
 #[derive(Clone)]
 pub struct Signal<T> {
     pub sample_rate: f64,
     pub time: i64,
+    pub meta: Option<SignalMeta>,
+    /// Sequence number and discontinuity marker, set by `AudioStream` on the chunks it delivers
+    pub stream: Option<StreamInfo>,
+    /// Events at sample indices, sorted by time, see `core::tag`
+    pub tags: Vec<Tag>,
     samples: Vec<Complex<T>>,
 }
 
@@ -83,6 +185,9 @@ impl<T: SignalType> Signal<T> {
         Signal {
             time: 0,
             sample_rate,
+            meta: None,
+            stream: None,
+            tags: Vec::new(),
             samples: Vec::new(),
         }
     }
@@ -99,6 +204,22 @@ impl<T: SignalType> Signal<T> {
         // read_wav_real(path)
         read_wav_complex(path)
     }
+    pub fn with_meta(mut self, meta: SignalMeta) -> Signal<T> {
+        self.meta = Some(meta);
+        self
+    }
+    /// Wall-clock time of the first sample, if the stream epoch is known
+    pub fn start_time(&self) -> Option<DateTime<Utc>> {
+        let epoch = self.meta.as_ref()?.epoch?;
+        Some(epoch + TimeDelta::nanoseconds((self.time as f64 / self.sample_rate * 1e9) as i64))
+    }
+    /// `label` extended with the metadata description
+    pub fn plot_label(&self, label: &str) -> String {
+        match &self.meta {
+            Some(meta) => format!("{label} [{}]", meta.describe()),
+            None => label.to_string(),
+        }
+    }
 }
 impl<T> Deref for Signal<T> {
     type Target = Vec<Complex<T>>;
@@ -112,3 +233,40 @@ impl<T> DerefMut for Signal<T> {
         &mut self.samples
     }
 }
+
+#[test]
+fn test_signal_meta() -> anyhow::Result<()> {
+    use crate::core::{block::filter::Filter, signal_ops::Align};
+
+    let epoch = DateTime::parse_from_rfc3339("2025-05-19T12:00:00Z")?.with_timezone(&Utc);
+    let meta = SignalMeta { epoch: Some(epoch), center_frequency: Some(25000.0), unit: Unit::Pascals, gain: 0.5 };
+    let mut sig = Signal::from_vec(1000.0, vec![1.0_f32; 2000]).with_meta(meta.clone());
+    sig.time = 1500;
+    assert_eq!(sig.start_time(), Some(epoch + TimeDelta::milliseconds(1500)));
+    assert_eq!(sig.plot_label("rx"), "rx [2025-05-19 12:00:00.000 UTC, fc=25000 Hz, 0.5 Pa]");
+
+    // Propagated by ops and filtering
+    assert_eq!(sig.add_aligned(&sig, Align::Union)?.meta, Some(meta.clone()));
+    let filtered = Filter::<f32>::lowpass(100.0, 32, 1000.0)?.process_and_finish(sig.clone()).unwrap();
+    assert_eq!(filtered.meta, Some(meta.clone()));
+
+    // WAV round trip through the sidecar
+    let dir = std::env::temp_dir().join("mulink_test_signal_meta");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("meta.wav");
+    let path = path.to_str().unwrap();
+    sig.write_wav(path, SampleFormat::Float, false)?;
+    let read = Signal::<f32>::read_wav(path)?;
+    assert_eq!(read.meta, Some(meta));
+    // Overwriting without metadata removes the old sidecar
+    Signal::from_vec(1000.0, vec![1.0_f32; 10]).write_wav(path, SampleFormat::Float, false)?;
+    assert_eq!(Signal::<f32>::read_wav(path)?.meta, None);
+    let mut real = crate::core::real_signal::RealSignal::from_vec(1000.0, vec![1.0_f32; 10]);
+    real.meta = Some(SignalMeta::default());
+    real.write_wav(path, SampleFormat::Int, true)?;
+    assert!(std::path::Path::new(&format!("{path}.meta")).exists());
+    real.meta = None;
+    real.write_wav(path, SampleFormat::Int, true)?;
+    assert!(!std::path::Path::new(&format!("{path}.meta")).exists());
+    Ok(())
+}
@@ -1,4 +1,8 @@
+use anyhow::anyhow;
 use itertools::Itertools;
+use num::Complex;
+
+use crate::core::tag::Tag;
 
 pub use crate::prelude::*;
 
@@ -19,34 +23,126 @@ impl<T: SignalType> Signal<T> {
         self
     }
 }
-impl<T: SignalType> std::ops::Mul for &Signal<T> {
-    type Output = Signal<T>;
-
-    fn mul(self, rhs: Self) -> Self::Output {
-        let mut out = Signal::from_vec(self.sample_rate, self.iter().zip(rhs.iter()).map(|(a,b)| a*b).collect_vec());
-        out.time = i64::max(self.time, rhs.time);
-        out
-    }
+/// How two signals with different `time` offsets / lengths are combined. Wherever only one
+/// operand has a sample, the other one counts as zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    /// Span of both operands, missing samples are treated as zero
+    Union,
+    /// Only the span covered by both operands
+    Intersection,
 }
-impl<T: SignalType> std::ops::Add for &Signal<T> {
-    type Output = Signal<T>;
 
-    fn add(self, rhs: Self) -> Self::Output {
-        let mut out = Signal::from_vec(self.sample_rate, self.iter().zip(rhs.iter()).map(|(a,b)| a+b).collect_vec());
-        out.time = i64::max(self.time, rhs.time);
-        out
+impl<T: SignalType> Signal<T> {
+    /// Sample at absolute stream `time`, if this signal covers it
+    pub fn at_time(&self, time: i64) -> Option<Complex<T>> {
+        usize::try_from(time - self.time).ok().and_then(|idx| self.get(idx).copied())
     }
-}
-impl<T: SignalType> std::ops::AddAssign for Signal<T> {
-    
-    fn add_assign(&mut self, rhs: Self) {
-        self.iter_mut().zip(rhs.iter()).for_each(|(a,b)| *a += b );
+    /// Combine two signals sample-by-sample after aligning them on `time`. `meta` comes from
+    /// `self`, the tags of both operands inside the output span are kept and `stream` is dropped.
+    pub fn zip_aligned(&self, rhs: &Signal<T>, align: Align, op: impl Fn(Complex<T>, Complex<T>) -> Complex<T>) -> anyhow::Result<Signal<T>> {
+        if self.sample_rate != rhs.sample_rate {
+            return Err(anyhow!("Sample rate mismatch: {} vs {}", self.sample_rate, rhs.sample_rate));
+        }
+        let (lhs_end, rhs_end) = (self.time + self.len() as i64, rhs.time + rhs.len() as i64);
+        let (start, end) = match align {
+            Align::Union => (i64::min(self.time, rhs.time), i64::max(lhs_end, rhs_end)),
+            Align::Intersection => (i64::max(self.time, rhs.time), i64::min(lhs_end, rhs_end)),
+        };
+        let samples = (start..i64::max(start, end)).map(|t| {
+            op(self.at_time(t).unwrap_or_default(), rhs.at_time(t).unwrap_or_default())
+        }).collect_vec();
+        let mut out = Signal::from_vec(self.sample_rate, samples);
+        out.time = start;
+        out.meta = self.meta.clone();
+        out.tags = merged_tags(&[self, rhs], start, end);
+        Ok(out)
+    }
+    pub fn add_aligned(&self, rhs: &Signal<T>, align: Align) -> anyhow::Result<Signal<T>> {
+        self.zip_aligned(rhs, align, |a, b| a + b)
+    }
+    pub fn sub_aligned(&self, rhs: &Signal<T>, align: Align) -> anyhow::Result<Signal<T>> {
+        self.zip_aligned(rhs, align, |a, b| a - b)
+    }
+    pub fn mul_aligned(&self, rhs: &Signal<T>, align: Align) -> anyhow::Result<Signal<T>> {
+        self.zip_aligned(rhs, align, |a, b| a * b)
+    }
+    /// In-place variant, `self` keeps its span and `rhs` is clipped to it. As with `Align`,
+    /// `rhs` counts as zero where it has no sample. The tags of `rhs` inside the span are added.
+    pub fn zip_assign_aligned(&mut self, rhs: &Signal<T>, op: impl Fn(&mut Complex<T>, Complex<T>)) -> anyhow::Result<()> {
+        if self.sample_rate != rhs.sample_rate {
+            return Err(anyhow!("Sample rate mismatch: {} vs {}", self.sample_rate, rhs.sample_rate));
+        }
+        let time = self.time;
+        self.iter_mut().enumerate().for_each(|(idx, a)| op(a, rhs.at_time(time + idx as i64).unwrap_or_default()));
+        self.tags = merged_tags(&[self, rhs], self.time, self.end_time());
+        Ok(())
+    }
+    /// `self += rhs` over the span of `self`
+    pub fn add_assign_aligned(&mut self, rhs: &Signal<T>) -> anyhow::Result<()> {
+        self.zip_assign_aligned(rhs, |a, b| *a += b)
+    }
+    /// `self *= rhs` over the span of `self`, zero where `rhs` has no sample
+    pub fn mul_assign_aligned(&mut self, rhs: &Signal<T>) -> anyhow::Result<()> {
+        self.zip_assign_aligned(rhs, |a, b| *a *= b)
     }
 }
 
-impl<T: SignalType> std::ops::MulAssign for Signal<T> {
-    
-    fn mul_assign(&mut self, rhs: Self) {
-        self.iter_mut().zip(rhs.iter()).for_each(|(a,b)| *a *= b );
-    }
-}
\ No newline at end of file
+/// Tags of `signals` in `[start, end)`, in time order
+fn merged_tags<T: SignalType>(signals: &[&Signal<T>], start: i64, end: i64) -> Vec<Tag> {
+    signals.iter().flat_map(|x| x.tags.iter()).filter(|tag| (start..end).contains(&tag.time)).cloned().sorted_by_key(|tag| tag.time).collect_vec()
+}
+
+#[test]
+fn test_signal_ops() -> anyhow::Result<()> {
+    use crate::core::tag::TagValue;
+
+    let mut a = Signal::from_vec(1000.0, vec![1.0_f64; 4]);
+    a.time = 10;
+    let mut b = Signal::from_vec(1000.0, vec![2.0_f64; 4]);
+    b.time = 12;
+
+    // a: 10..14, b: 12..16
+    let sum = a.add_aligned(&b, Align::Union)?;
+    assert_eq!(sum.time, 10);
+    assert_eq!(sum.re(), vec![1.0, 1.0, 3.0, 3.0, 2.0, 2.0]);
+
+    let prod = a.mul_aligned(&b, Align::Intersection)?;
+    assert_eq!(prod.time, 12);
+    assert_eq!(prod.re(), vec![2.0, 2.0]);
+
+    // Disjoint intersection is empty but keeps a sane time
+    let mut c = b.clone();
+    c.time = 100;
+    assert!(a.add_aligned(&c, Align::Intersection)?.is_empty());
+
+    // In-place ops keep the span of the left operand
+    let mut d = a.clone();
+    d.add_assign_aligned(&b)?;
+    assert_eq!(d.time, 10);
+    assert_eq!(d.re(), vec![1.0, 1.0, 3.0, 3.0]);
+
+    // Missing samples count as zero, in place too
+    assert_eq!(a.mul_aligned(&b, Align::Union)?.re(), vec![0.0, 0.0, 2.0, 2.0, 0.0, 0.0]);
+    let mut f = a.clone();
+    f.mul_assign_aligned(&b)?;
+    assert_eq!((f.time, f.re()), (10, vec![0.0, 0.0, 2.0, 2.0]));
+
+    // Tags of both operands inside the output span are kept
+    let mut g = a.clone();
+    g.add_tag(0, "a", TagValue::Flag);
+    let mut h = b.clone();
+    h.add_tag(0, "b", TagValue::Flag);
+    h.add_tag(3, "late", TagValue::Flag);
+    let tags = |x: &Signal<f64>| x.tags.iter().map(|tag| (tag.time, tag.key.clone())).collect_vec();
+    assert_eq!(tags(&g.add_aligned(&h, Align::Intersection)?), vec![(12, "b".to_string())]);
+    assert_eq!(tags(&g.add_aligned(&h, Align::Union)?), vec![(10, "a".to_string()), (12, "b".to_string()), (15, "late".to_string())]);
+    g.add_assign_aligned(&h)?;
+    assert_eq!(tags(&g), vec![(10, "a".to_string()), (12, "b".to_string())]);
+
+    // Sample rate mismatch is an error
+    let e = Signal::from_vec(2000.0, vec![1.0_f64; 4]);
+    assert!(a.add_aligned(&e, Align::Union).is_err());
+    assert!(d.zip_assign_aligned(&e, |x, y| *x += y).is_err());
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use anyhow::anyhow;
+use num::{Complex, Zero};
+
+use crate::prelude::*;
+
+// Windowing / splicing that keeps `time` and `sample_rate` consistent.
+// Ranges are clipped to the samples that exist.
+
+impl<T: SignalType> Signal<T> {
+    /// Stream time one past the last sample
+    pub fn end_time(&self) -> i64 {
+        self.time + self.len() as i64
+    }
+    /// Length in seconds
+    pub fn duration(&self) -> f64 {
+        self.len() as f64 / self.sample_rate
+    }
+    /// Copy of `range` (sample indices into this signal)
+    pub fn slice(&self, range: Range<usize>) -> Signal<T> {
+        let end = usize::min(range.end, self.len());
+        let start = usize::min(range.start, end);
+        let mut out = Signal::from_vec(self.sample_rate, self[start..end].to_vec());
+        out.time = self.time + start as i64;
+        out.meta = self.meta.clone();
+        let end_time = out.end_time();
+        out.tags = self.tags.iter().filter(|tag| (out.time..end_time).contains(&tag.time)).cloned().collect();
+        out
+    }
+    /// Copy of the absolute stream time window `[start, end)`
+    pub fn slice_time(&self, start: i64, end: i64) -> Signal<T> {
+        let to_idx = |t: i64| (t - self.time).clamp(0, self.len() as i64) as usize;
+        self.slice(to_idx(start)..to_idx(end))
+    }
+    /// Copy of the window `[start, end)` in seconds of stream time (`time / sample_rate`)
+    pub fn slice_seconds(&self, start: f64, end: f64) -> Signal<T> {
+        self.slice_time((start * self.sample_rate).round() as i64, (end * self.sample_rate).round() as i64)
+    }
+    /// `[0, idx)` and `[idx, len)`
+    pub fn split_at(&self, idx: usize) -> (Signal<T>, Signal<T>) {
+        (self.slice(0..idx), self.slice(idx..self.len()))
+    }
+    /// Split into consecutive chunks of `len` samples, the last one may be shorter.
+    /// A `len` of 0 is taken as 1.
+    pub fn split_chunks(&self, len: usize) -> Vec<Signal<T>> {
+        let len = len.max(1);
+        (0..self.len()).step_by(len).map(|start| self.slice(start..start + len)).collect()
+    }
+    /// Append `other`, which must start where `self` ends. An empty `self` adopts the time of `other`.
+    pub fn concat(&mut self, other: &Signal<T>) -> anyhow::Result<()> {
+        if self.sample_rate != other.sample_rate {
+            return Err(anyhow!("Sample rate mismatch: {} vs {}", self.sample_rate, other.sample_rate));
+        }
+        if self.is_empty() {
+            self.time = other.time;
+        } else if other.time != self.end_time() {
+            return Err(anyhow!("Signals are not contiguous: {} vs {}", self.end_time(), other.time));
+        }
+        self.extend_from_slice(other);
+        self.tags.extend(other.tags.iter().cloned());
+        Ok(())
+    }
+    /// Concatenate contiguous signals, see `concat`
+    pub fn concat_all<'a>(signals: impl IntoIterator<Item = &'a Signal<T>>) -> anyhow::Result<Signal<T>> {
+        let mut signals = signals.into_iter();
+        let Some(first) = signals.next() else {
+            return Err(anyhow!("Nothing to concatenate"));
+        };
+        let mut out = first.clone();
+        signals.try_for_each(|x| out.concat(x))?;
+        Ok(out)
+    }
+    /// Zero pad, `time` moves back by `before`
+    pub fn pad(&mut self, before: usize, after: usize) {
+        self.splice(0..0, std::iter::repeat_n(Complex::zero(), before));
+        let len = self.len() + after;
+        self.resize(len, Complex::zero());
+        self.time -= before as i64;
+    }
+    /// Remove samples from both ends, `time` moves forward by the amount trimmed at the front
+    pub fn trim(&mut self, before: usize, after: usize) {
+        let before = usize::min(before, self.len());
+        self.drain(0..before);
+        let len = self.len().saturating_sub(after);
+        self.truncate(len);
+        self.time += before as i64;
+        let range = self.time..self.end_time();
+        self.tags.retain(|tag| range.contains(&tag.time));
+    }
+}
+
+#[test]
+fn test_signal_slice() -> anyhow::Result<()> {
+    let mut sig = Signal::from_vec(1000.0, (0..100).map(|x| x as f64).collect::<Vec<_>>());
+    sig.time = 1000;
+
+    let part = sig.slice(10..20);
+    assert_eq!((part.time, part.len(), part[0].re), (1010, 10, 10.0));
+
+    let part = sig.slice_time(1090, 1200);
+    assert_eq!((part.time, part.len()), (1090, 10));
+
+    // 1.05 s is stream sample 1050
+    let part = sig.slice_seconds(1.05, 1.06);
+    assert_eq!((part.time, part.len(), part[0].re), (1050, 10, 50.0));
+
+    let (a, b) = sig.split_at(40);
+    assert_eq!((a.time, a.len(), b.time, b.len()), (1000, 40, 1040, 60));
+    let joined = Signal::concat_all(&sig.split_chunks(32))?;
+    assert_eq!((joined.time, joined.re()), (sig.time, sig.re()));
+    assert_eq!(sig.slice(0..3).split_chunks(0).iter().map(|x| (x.time, x.len())).collect::<Vec<_>>(), vec![(1000, 1), (1001, 1), (1002, 1)]);
+
+    // Only contiguous signals can be concatenated
+    let mut a = a;
+    assert!(a.concat(&b.slice(1..10)).is_err());
+
+    let mut padded = sig.clone();
+    padded.pad(5, 3);
+    assert_eq!((padded.time, padded.len(), padded[5].re), (995, 108, 0.0));
+    padded.trim(5, 3);
+    assert_eq!((padded.time, padded.re()), (sig.time, sig.re()));
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use anyhow::anyhow;
+use num::{Complex, Zero};
+
+use crate::{core::{block::fft::{FftInst, RustFftInst}, signal::Amplitude}, prelude::*};
+
+// Level measurements. Amplitude-like results are `Amplitude::Linear`, call `.db()` / `.to_log()` for dB.
+// Power-like results use `Amplitude::from_power`, so `.db()` gives 10*log10(power).
+
+impl<T: SignalType> Signal<T> {
+    /// Sum of |x|^2
+    pub fn energy(&self) -> f64 {
+        self.iter().map(|x| -> f64 { x.norm_sqr().as_() }).sum()
+    }
+    /// Mean of |x|^2
+    pub fn mean_power(&self) -> Amplitude {
+        Amplitude::from_power(if self.is_empty() { 0.0 } else { self.energy() / self.len() as f64 })
+    }
+    pub fn rms(&self) -> Amplitude {
+        self.mean_power().to_linear()
+    }
+    /// Largest |x|
+    pub fn peak(&self) -> Amplitude {
+        Amplitude::Linear(self.iter().map(|x| -> f64 { x.norm().as_() }).fold(0.0, f64::max))
+    }
+    /// Peak to RMS ratio
+    pub fn crest_factor(&self) -> Amplitude {
+        Amplitude::Linear(self.peak().linear::<f64>() / self.rms().linear::<f64>())
+    }
+    /// Mean sample value
+    pub fn dc_offset(&self) -> Complex<T> {
+        if self.is_empty() {
+            return Complex::zero();
+        }
+        self.iter().fold(Complex::zero(), |a: Complex<T>, b| a + b) / T::from_usize(self.len()).unwrap()
+    }
+    /// Mean power of the components with frequency in `[low, high]` Hz (negative frequencies are below 0 Hz).
+    /// The bands of all power measurements add up to `mean_power`.
+    pub fn band_power(&self, low: f64, high: f64) -> anyhow::Result<Amplitude> {
+        band_power(self, low, high, false)
+    }
+}
+
+fn band_power<T: SignalType>(signal: &Signal<T>, low: f64, high: f64, mirror: bool) -> anyhow::Result<Amplitude> {
+    if low > high {
+        return Err(anyhow!("Invalid band [{low}, {high}] Hz"));
+    }
+    let len = signal.len();
+    if len == 0 {
+        return Ok(Amplitude::from_power(0.0));
+    }
+    let mut spectrum = signal.to_vec();
+    RustFftInst::<T>::new(len).fft_fwd(&mut spectrum)?;
+    // Unitary FFT, so Parseval holds bin by bin
+    let power: f64 = spectrum.iter().enumerate().filter(|(idx, _)| {
+        let freq = if 2 * idx < len { *idx as f64 } else { *idx as f64 - len as f64 } * signal.sample_rate / len as f64;
+        let freq = if mirror { freq.abs() } else { freq };
+        (low..=high).contains(&freq)
+    }).map(|(_, x)| -> f64 { x.norm_sqr().as_() }).sum();
+    Ok(Amplitude::from_power(power / len as f64))
+}
+
+impl<T: SignalType> RealSignal<T> {
+    pub fn energy(&self) -> f64 {
+        self.iter().map(|x| -> f64 { (*x * *x).as_() }).sum()
+    }
+    pub fn mean_power(&self) -> Amplitude {
+        Amplitude::from_power(if self.is_empty() { 0.0 } else { self.energy() / self.len() as f64 })
+    }
+    pub fn rms(&self) -> Amplitude {
+        self.mean_power().to_linear()
+    }
+    pub fn peak(&self) -> Amplitude {
+        Amplitude::Linear(self.iter().map(|x| -> f64 { x.abs().as_() }).fold(0.0, f64::max))
+    }
+    pub fn crest_factor(&self) -> Amplitude {
+        Amplitude::Linear(self.peak().linear::<f64>() / self.rms().linear::<f64>())
+    }
+    pub fn dc_offset(&self) -> T {
+        if self.is_empty() {
+            return T::zero();
+        }
+        self.iter().fold(T::zero(), |a, b| a + *b) / T::from_usize(self.len()).unwrap()
+    }
+    /// Mean power in `[low, high]` Hz, both spectral mirrors of a real signal are counted
+    pub fn band_power(&self, low: f64, high: f64) -> anyhow::Result<Amplitude> {
+        band_power(&self.to_complex(), low, high, true)
+    }
+}
+
+#[test]
+fn test_stats() -> anyhow::Result<()> {
+    use std::f64::consts::PI;
+    use crate::core::signal::FromFunction;
+
+    // 0.5 amplitude sine + 0.1 DC
+    let sig = RealSignal::from_function(48000.0, 48000, |x| 0.5 * f64::sin(1000.0 * 2.0 * PI * x) + 0.1);
+    assert!((sig.dc_offset() - 0.1).abs() < 1e-9);
+    assert!((sig.peak().linear::<f64>() - 0.6).abs() < 1e-6);
+    assert!((sig.mean_power().power() - (0.125 + 0.01)).abs() < 1e-9);
+    assert!((sig.rms().linear::<f64>() - f64::sqrt(0.135)).abs() < 1e-9);
+    assert!((sig.energy() - 0.135 * 48000.0).abs() < 1e-6);
+    assert!((sig.crest_factor().linear::<f64>() - 0.6 / f64::sqrt(0.135)).abs() < 1e-6);
+
+    // Band power splits the tone from DC and adds up to the total
+    let tone = sig.band_power(900.0, 1100.0)?;
+    assert!((tone.power() - 0.125).abs() < 1e-9);
+    assert!((tone.db() - 10.0 * f64::log10(0.125)).abs() < 1e-9);
+    let dc = sig.band_power(0.0, 10.0)?;
+    assert!((dc.power() - 0.01).abs() < 1e-9);
+
+    // Complex tone only occupies its own side of the spectrum
+    let sig = Signal::from_function(48000.0, 4800, |x| Complex::new(f64::cos(1000.0 * 2.0 * PI * x), f64::sin(1000.0 * 2.0 * PI * x)));
+    assert!((sig.rms().db()).abs() < 1e-9);
+    assert!((sig.band_power(900.0, 1100.0)?.power() - 1.0).abs() < 1e-9);
+    assert!(sig.band_power(-1100.0, -900.0)?.power() < 1e-12);
+    Ok(())
+}
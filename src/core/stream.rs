@@ -1,65 +1,313 @@
 use core::{f32, f64};
 use std::{
+    cell::Cell,
+    collections::VecDeque,
     marker::PhantomData,
-    sync::{atomic::AtomicI64, Arc, Mutex, MutexGuard, PoisonError},
-    thread,
+    ops::Deref,
+    pin::Pin,
+    sync::{atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering}, Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll},
+    thread::{self, JoinHandle},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
-use crossbeam::channel::{Receiver, Sender};
+use crossbeam::channel::{Receiver, Sender, TryRecvError, TrySendError};
+use futures::task::AtomicWaker;
 use log::{info, trace};
+use num::Zero;
 
 use crate::{core::signal::{FromFunction, FromVec, Signal, SignalType}, logging::init_tracing};
 
+/// What a bounded subscriber does when its queue is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait for the subscriber. This stalls delivery to every subscriber of the topic, and the
+    /// publisher too once the topic's own queue (see `Topic::with_capacity`) is full.
+    Block,
+    /// Discard the oldest queued message to make room
+    DropOldest,
+    /// Discard the incoming message
+    DropNewest,
+}
+
+/// Receiving end of a `Topic`. Derefs to the underlying `Receiver`.
+pub struct Subscriber<T> {
+    recv: Receiver<Arc<T>>,
+    dropped: Arc<AtomicUsize>,
+    closed: Arc<AtomicBool>,
+    waker: Arc<AtomicWaker>,
+}
+impl<T> Subscriber<T> {
+    /// Receive as a `futures::Stream` instead, keeping the overflow policy and any history
+    pub fn into_async(self) -> AsyncSubscriber<T> {
+        AsyncSubscriber { sub: self }
+    }
+    /// Messages discarded for this subscriber by its overflow policy
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+    /// End-of-stream: the topic was closed and every message delivered to this subscriber was received
+    pub fn is_finished(&self) -> bool {
+        self.closed.load(Ordering::Acquire) && self.recv.is_empty()
+    }
+}
+impl<T> Deref for Subscriber<T> {
+    type Target = Receiver<Arc<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.recv
+    }
+}
+
+/// `Subscriber` as a `futures::Stream`, ends when the topic is closed. Executor agnostic:
+/// the dispatch thread wakes the task, no thread is needed per subscriber.
+/// `Topic::close` blocks until `Overflow::Block` subscribers take their messages, so do not
+/// call it from the task that consumes one.
+pub struct AsyncSubscriber<T> {
+    sub: Subscriber<T>,
+}
+impl<T> AsyncSubscriber<T> {
+    pub fn dropped(&self) -> usize {
+        self.sub.dropped()
+    }
+    pub fn is_finished(&self) -> bool {
+        self.sub.is_finished()
+    }
+}
+impl<T> futures::Stream for AsyncSubscriber<T> {
+    type Item = Arc<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Arc<T>>> {
+        // Register before the second attempt so a message delivered in between is not missed
+        for attempt in 0..2 {
+            match self.sub.recv.try_recv() {
+                Ok(msg) => return Poll::Ready(Some(msg)),
+                Err(TryRecvError::Disconnected) => return Poll::Ready(None),
+                Err(TryRecvError::Empty) if attempt == 0 => self.sub.waker.register(cx.waker()),
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// Daemon side of a `Subscriber`
+struct SubscriberTx<T> {
+    send: Sender<Arc<T>>,
+    /// Only kept under `Overflow::DropOldest`, to evict the oldest message. Any other
+    /// subscriber's channel disconnects when the `Subscriber` is dropped.
+    recv: Option<Receiver<Arc<T>>>,
+    overflow: Overflow,
+    dropped: Arc<AtomicUsize>,
+    /// Declared last so it is dropped after `send`
+    waker: WakeOnDrop,
+}
+/// Dropping the sender is end-of-stream, an async subscriber has to see it.
+/// Waking from `SubscriberTx::drop` would be too early, the channel is still connected then.
+struct WakeOnDrop(Arc<AtomicWaker>);
+impl Drop for WakeOnDrop {
+    fn drop(&mut self) {
+        self.0.wake();
+    }
+}
+impl<T> SubscriberTx<T> {
+    /// `false` once the subscriber is gone
+    fn deliver(&self, msg: Arc<T>) -> bool {
+        let alive = self.offer(msg);
+        self.waker.0.wake();
+        alive
+    }
+    fn offer(&self, msg: Arc<T>) -> bool {
+        match self.overflow {
+            Overflow::Block => {
+                // An async subscriber has to run to make room
+                self.waker.0.wake();
+                self.send.send(msg).is_ok()
+            }
+            Overflow::DropNewest => match self.send.try_send(msg) {
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                result => result.is_ok(),
+            },
+            Overflow::DropOldest => {
+                // Our own receiver keeps the channel connected, but the daemon holds the only
+                // other reference to `dropped` once the `Subscriber` is gone
+                if Arc::strong_count(&self.dropped) == 1 {
+                    return false;
+                }
+                let mut msg = msg;
+                loop {
+                    match self.send.try_send(msg) {
+                        Err(TrySendError::Full(rejected)) => {
+                            if self.recv.as_ref().is_some_and(|recv| recv.try_recv().is_ok()) {
+                                self.dropped.fetch_add(1, Ordering::Relaxed);
+                            }
+                            msg = rejected;
+                        }
+                        result => return result.is_ok(),
+                    }
+                }
+            }
+        }
+    }
+}
+
+type HistoryTrim<T> = Box<dyn FnMut(&mut VecDeque<Arc<T>>) + Send>;
+
+/// Recent messages a `Topic` keeps for late subscribers
+struct History<T> {
+    msgs: VecDeque<Arc<T>>,
+    /// Called after every dispatched message, drops what no longer needs to be kept
+    trim: HistoryTrim<T>,
+}
+
+/// Fan-out of published messages to subscribers on a daemon thread.
+/// Dropping the topic (or `close`) drains queued messages and then ends every subscriber's stream.
 pub struct Topic<T> {
-    subscribers: Arc<Mutex<Vec<Sender<Arc<T>>>>>,
+    subscribers: Arc<Mutex<Vec<Arc<SubscriberTx<T>>>>>,
+    /// Locked after `subscribers` when both are needed
+    history: Option<Arc<Mutex<History<T>>>>,
     publisher: Sender<T>,
+    /// Woken whenever the daemon takes a message, i.e. a bounded topic has room again
+    space: Arc<AtomicWaker>,
+    shutdown: Sender<()>,
+    daemon: Mutex<Option<JoinHandle<()>>>,
+    closed: Arc<AtomicBool>,
 }
 impl<T: Send + Sync + Clone + 'static> Topic<T> {
     pub fn new() -> Self {
-        let (send, recv) = crossbeam::channel::unbounded::<T>();
-        let topic = Topic {
+        Self::from_channel(crossbeam::channel::unbounded::<T>(), None)
+    }
+    /// Publishers block once `capacity` messages are waiting to be dispatched
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from_channel(crossbeam::channel::bounded::<T>(capacity), None)
+    }
+    /// Keep recently dispatched messages, see `history` and `get_subscriber_with_history`.
+    /// `trim` runs after each message is added and removes the ones no longer wanted.
+    pub fn with_history(trim: impl FnMut(&mut VecDeque<Arc<T>>) + Send + 'static) -> Self {
+        Self::from_channel(crossbeam::channel::unbounded::<T>(), Some(History { msgs: VecDeque::new(), trim: Box::new(trim) }))
+    }
+    fn from_channel((send, recv): (Sender<T>, Receiver<T>), history: Option<History<T>>) -> Self {
+        let (shutdown, shutdown_recv) = crossbeam::channel::bounded::<()>(1);
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let history = history.map(|x| Arc::new(Mutex::new(x)));
+        let closed = Arc::new(AtomicBool::new(false));
+        let space = Arc::new(AtomicWaker::new());
+        let daemon = Self::launch_daemon(subscribers.clone(), history.clone(), recv, shutdown_recv, closed.clone(), space.clone());
+        Topic {
             publisher: send,
-            subscribers: Arc::new(Mutex::new(Vec::new())),
-        };
-        Self::launch_daemon(topic.subscribers.clone(), recv);
-        topic
+            space,
+            subscribers,
+            history,
+            shutdown,
+            daemon: Mutex::new(Some(daemon)),
+            closed,
+        }
+    }
+    /// Unbounded subscriber, never drops
+    pub fn get_subscriber(&self) -> Subscriber<T> {
+        self.subscribe(crossbeam::channel::unbounded::<Arc<T>>(), Overflow::Block)
+    }
+    /// Subscriber holding at most `capacity` (at least 1) undelivered messages
+    pub fn get_bounded_subscriber(&self, capacity: usize, overflow: Overflow) -> Subscriber<T> {
+        self.subscribe(crossbeam::channel::bounded::<Arc<T>>(capacity.max(1)), overflow)
     }
-    pub fn get_subscriber(&self) -> Receiver<Arc<T>> {
-        let (send, recv) = crossbeam::channel::unbounded::<Arc<T>>();
-        self.subscribers.lock().unwrap().push(send);
-        recv
+    /// Unbounded subscriber that first receives the retained history, then every later message
+    /// without duplicates or gaps. Same as `get_subscriber` for a topic without history.
+    pub fn get_subscriber_with_history(&self) -> Subscriber<T> {
+        self.subscribe_from(crossbeam::channel::unbounded::<Arc<T>>(), Overflow::Block, true)
+    }
+    /// Snapshot of the retained messages, oldest first
+    pub fn history(&self) -> Vec<Arc<T>> {
+        self.history.as_ref().map(|history| history.lock().unwrap().msgs.iter().cloned().collect()).unwrap_or_default()
+    }
+    fn subscribe(&self, channel: (Sender<Arc<T>>, Receiver<Arc<T>>), overflow: Overflow) -> Subscriber<T> {
+        self.subscribe_from(channel, overflow, false)
+    }
+    fn subscribe_from(&self, (send, recv): (Sender<Arc<T>>, Receiver<Arc<T>>), overflow: Overflow, replay: bool) -> Subscriber<T> {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let waker = Arc::new(AtomicWaker::new());
+        let mut subs = self.subscribers.lock().unwrap();
+        // Holding `subscribers` keeps the daemon from dispatching between the replay and registration
+        if let Some(history) = self.history.as_ref().filter(|_| replay) {
+            history.lock().unwrap().msgs.iter().for_each(|msg| {
+                let _ = send.send(msg.clone());
+            });
+        }
+        // A subscriber of a closed topic is at end-of-stream straight away
+        if !self.closed.load(Ordering::Acquire) {
+            let evict = (overflow == Overflow::DropOldest).then(|| recv.clone());
+            subs.push(Arc::new(SubscriberTx { send, recv: evict, overflow, dropped: dropped.clone(), waker: WakeOnDrop(waker.clone()) }));
+        }
+        Subscriber { recv, dropped, closed: self.closed.clone(), waker }
     }
+    /// Sending fails once the topic is closed
     pub fn get_publisher(&self) -> Sender<T> {
         self.publisher.clone()
     }
-    fn launch_daemon(subs: Arc<Mutex<Vec<Sender<Arc<T>>>>>, recv: Receiver<T>) {
+    /// Stop the daemon after dispatching everything already published, then join it.
+    /// Subscribers still receive queued messages, after which `recv` fails (end-of-stream).
+    /// Waits for `Overflow::Block` subscribers to make room.
+    pub fn close(&self) -> Result<()> {
+        let _ = self.shutdown.try_send(());
+        let daemon = self.daemon.lock().map_err(|_| anyhow!("mulink-dsp::topic_lock_failure"))?.take();
+        if let Some(daemon) = daemon {
+            daemon.join().map_err(|_| anyhow!("mulink-dsp::topic_daemon_panicked"))?;
+        }
+        Ok(())
+    }
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+    /// Delivers to a snapshot of the subscribers, so a blocking subscriber does not hold the
+    /// lock and `get_subscriber` or `history` can still be called while it catches up
+    fn dispatch(subs: &Mutex<Vec<Arc<SubscriberTx<T>>>>, history: Option<&Mutex<History<T>>>, msg: T) {
+        let msg = Arc::new(msg);
+        let snapshot = {
+            let subs = subs.lock().unwrap();
+            if let Some(history) = history {
+                let mut history = history.lock().unwrap();
+                let History { msgs, trim } = &mut *history;
+                msgs.push_back(msg.clone());
+                trim(msgs);
+            }
+            subs.clone()
+        };
+        trace!(
+            "Dispatching message from Topic<T> to {} subscribers",
+            snapshot.len()
+        );
+        let gone = snapshot.into_iter().filter(|sub| !sub.deliver(msg.clone())).collect::<Vec<_>>();
+        if !gone.is_empty() {
+            subs.lock().unwrap().retain(|sub| !gone.iter().any(|x| Arc::ptr_eq(sub, x)));
+        }
+    }
+    fn launch_daemon(subs: Arc<Mutex<Vec<Arc<SubscriberTx<T>>>>>, history: Option<Arc<Mutex<History<T>>>>, recv: Receiver<T>, shutdown: Receiver<()>, closed: Arc<AtomicBool>, space: Arc<AtomicWaker>) -> JoinHandle<()> {
         thread::spawn(move || {
-            let subs = subs.clone();
             'service_topic: loop {
-                let msg = recv.recv();
+                // `shutdown` also fires when the topic is dropped
+                let msg = crossbeam::select! {
+                    recv(recv) -> msg => msg,
+                    recv(shutdown) -> _ => break 'service_topic,
+                };
                 let Ok(msg) = msg else {
                     break 'service_topic;
                 };
-                let msg = Arc::new(msg);
-
-                let mut subs = subs.lock().unwrap();
-                trace!(
-                    "Dispatching message from Topic<T> to {} subscribers",
-                    subs.len()
-                );
-                subs.retain_mut(|sub| {
-                    let result = sub.send(msg.clone());
-                    if result.is_ok() {
-                        true
-                    } else {
-                        //info!("dropped a sub");
-                        false
-                    }
-                });
+                space.wake();
+                Self::dispatch(&subs, history.as_deref(), msg);
             }
-        });
+            for msg in recv.try_iter() {
+                Self::dispatch(&subs, history.as_deref(), msg);
+            }
+            // Dropping the senders disconnects the subscribers once they have drained their queues
+            let mut subs = subs.lock().unwrap();
+            closed.store(true, Ordering::Release);
+            subs.clear();
+            trace!("Topic<T> daemon stopped");
+        })
     }
 }
 impl<T: Send + Sync + Clone + 'static> Default for Topic<T> {
@@ -67,17 +315,143 @@ impl<T: Send + Sync + Clone + 'static> Default for Topic<T> {
         Self::new()
     }
 }
+/// Break in the sample sequence of a stream, reported on the first chunk after it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Discontinuity {
+    /// `samples` were lost before this chunk (device overrun, dropped chunks). `time` already skips them.
+    Gap { samples: i64 },
+    /// The producer restarted, this chunk is unrelated to the previous ones
+    Reset,
+}
+impl Discontinuity {
+    /// Combined marker when several discontinuities happen before one chunk
+    pub fn merge(self, other: Discontinuity) -> Discontinuity {
+        match (self, other) {
+            (Discontinuity::Gap { samples: a }, Discontinuity::Gap { samples: b }) => Discontinuity::Gap { samples: a + b },
+            _ => Discontinuity::Reset,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Increments by one per chunk sent into the stream
+    pub seq: u64,
+    pub discontinuity: Option<Discontinuity>,
+}
+
 pub struct AudioTxGuard<T: SignalType> {
     tx: Sender<Signal<T>>,
+    space: Arc<AtomicWaker>,
     time: Arc<AtomicI64>,
+    seq: Cell<u64>,
+    pending: Cell<Option<Discontinuity>>,
 }
 impl<T: SignalType> AudioTxGuard<T> {
-    pub fn send(&self, mut sig: Signal<T>) -> anyhow::Result<()> {
-        sig.time = self.time.load(std::sync::atomic::Ordering::Relaxed);
-        self.time.fetch_add(sig.len() as i64, std::sync::atomic::Ordering::Relaxed);
-        Ok(self.tx.send(sig)?)
+    fn mark(&self, discontinuity: Discontinuity) {
+        self.pending.set(Some(self.pending.get().map_or(discontinuity, |x| x.merge(discontinuity))));
+    }
+    /// Assign stream time, sequence number and pending marker, taken by `commit` once sent
+    fn stamp(&self, sig: Signal<T>) -> Signal<T> {
+        self.stamp_at(sig, self.time.load(std::sync::atomic::Ordering::Relaxed), self.pending.get())
+    }
+    fn stamp_at(&self, mut sig: Signal<T>, time: i64, discontinuity: Option<Discontinuity>) -> Signal<T> {
+        // Tags move with the samples
+        sig.retime(time);
+        sig.stream = Some(StreamInfo { seq: self.seq.get(), discontinuity });
+        sig
+    }
+    /// Advance past a chunk that was sent, a failed send leaves no trace in time or sequence
+    fn commit(&self, len: usize) {
+        self.time.fetch_add(len as i64, std::sync::atomic::Ordering::Relaxed);
+        self.seq.set(self.seq.get() + 1);
+        self.pending.set(None);
+    }
+    pub fn send(&self, sig: Signal<T>) -> anyhow::Result<()> {
+        let sig = self.stamp(sig);
+        let len = sig.len();
+        self.tx.send(sig)?;
+        self.commit(len);
+        Ok(())
+    }
+    /// `send` without blocking, a full stream hands the chunk back
+    fn try_send(&self, sig: Signal<T>) -> anyhow::Result<Option<Signal<T>>> {
+        let sig = self.stamp(sig);
+        let len = sig.len();
+        match self.tx.try_send(sig) {
+            Ok(()) => self.commit(len),
+            Err(TrySendError::Full(rejected)) => return Ok(Some(rejected)),
+            Err(TrySendError::Disconnected(_)) => return Err(anyhow!("mulink-dsp::audiostream_closed")),
+        }
+        Ok(None)
+    }
+    /// Send a chunk whose first sample is known to be at `time` (e.g. from a device timestamp).
+    /// A jump forward is reported as a `Gap`, a jump backwards as a `Reset`. The first chunk of a
+    /// stream just sets where it starts.
+    pub fn send_at(&self, sig: Signal<T>, time: i64) -> anyhow::Result<()> {
+        let expected = self.time.load(std::sync::atomic::Ordering::Relaxed);
+        let jump = match time.cmp(&expected) {
+            _ if self.seq.get() == 0 => None,
+            std::cmp::Ordering::Greater => Some(Discontinuity::Gap { samples: time - expected }),
+            std::cmp::Ordering::Less => Some(Discontinuity::Reset),
+            std::cmp::Ordering::Equal => None,
+        };
+        let discontinuity = match (self.pending.get(), jump) {
+            (Some(pending), Some(jump)) => Some(pending.merge(jump)),
+            (pending, jump) => pending.or(jump),
+        };
+        // Nothing is re-timed or marked as reported until the chunk is sent
+        let sig = self.stamp_at(sig, time, discontinuity);
+        let len = sig.len();
+        self.tx.send(sig)?;
+        self.time.store(time, std::sync::atomic::Ordering::Relaxed);
+        self.commit(len);
+        Ok(())
+    }
+    /// The producer lost `samples` (e.g. a device overrun). Stream time skips them and the next chunk carries a `Gap`.
+    pub fn overrun(&self, samples: usize) {
+        self.time.fetch_add(samples as i64, std::sync::atomic::Ordering::Relaxed);
+        self.mark(Discontinuity::Gap { samples: samples as i64 });
+    }
+    /// The producer restarted, the next chunk carries a `Reset`
+    pub fn reset(&self) {
+        self.mark(Discontinuity::Reset);
     }
 }
+
+/// Subscriber-side check of `StreamInfo`. Besides the producer's markers it reports chunks this
+/// subscriber missed (e.g. dropped by its overflow policy) as a `Gap`.
+#[derive(Clone, Debug, Default)]
+pub struct StreamMonitor {
+    next_seq: Option<u64>,
+    next_time: Option<i64>,
+}
+impl StreamMonitor {
+    pub fn new() -> StreamMonitor {
+        StreamMonitor::default()
+    }
+    pub fn check<T: SignalType>(&mut self, sig: &Signal<T>) -> Option<Discontinuity> {
+        let info = sig.stream?;
+        let mut discontinuity = info.discontinuity;
+        if discontinuity != Some(Discontinuity::Reset) && self.next_seq.is_some_and(|seq| seq != info.seq) {
+            // Missed chunks, the time difference covers any gap the producer reported in them as well
+            let samples = self.next_time.map_or(0, |time| sig.time - time);
+            discontinuity = Some(if samples > 0 { Discontinuity::Gap { samples } } else { Discontinuity::Reset });
+        }
+        self.next_seq = Some(info.seq + 1);
+        self.next_time = Some(sig.end_time());
+        discontinuity
+    }
+    /// `check` and write the result back into `sig.stream`, so blocks see subscriber-side gaps too
+    pub fn annotate<T: SignalType>(&mut self, sig: &mut Signal<T>) -> Option<Discontinuity> {
+        let discontinuity = self.check(sig);
+        if let Some(info) = sig.stream.as_mut() {
+            info.discontinuity = discontinuity;
+        }
+        discontinuity
+    }
+}
+
 pub struct AudioStream<T: SignalType> {
     tx: Mutex<AudioTxGuard<T>>,
     topic: Topic<Signal<T>>,
@@ -86,18 +460,101 @@ pub struct AudioStream<T: SignalType> {
 impl<T: SignalType> AudioStream<T> {
     pub fn new() -> AudioStream<T> {
         trace!("AudioStream::new()");
-        let topic = Topic::<Signal<T>>::new();
+        Self::from_topic(Topic::<Signal<T>>::new())
+    }
+    /// Retain at least the last `seconds` of audio for `fetch` and `get_subscriber_with_history`.
+    /// A `Reset` discards what came before it.
+    pub fn with_history(seconds: f64) -> AudioStream<T> {
+        Self::from_topic(Topic::<Signal<T>>::with_history(move |history: &mut VecDeque<Arc<Signal<T>>>| {
+            let Some(newest) = history.back().cloned() else {
+                return;
+            };
+            if newest.stream.and_then(|info| info.discontinuity) == Some(Discontinuity::Reset) {
+                history.retain(|x| Arc::ptr_eq(x, &newest));
+            }
+            // The oldest chunk goes once the next one alone reaches back far enough
+            let start = newest.end_time() - (seconds * newest.sample_rate).ceil() as i64;
+            while history.get(1).is_some_and(|x| x.time <= start) {
+                history.pop_front();
+            }
+        }))
+    }
+    /// `send` blocks once `capacity` chunks are waiting to be dispatched, see `Overflow::Block`
+    pub fn with_capacity(capacity: usize) -> AudioStream<T> {
+        Self::from_topic(Topic::<Signal<T>>::with_capacity(capacity))
+    }
+    fn from_topic(topic: Topic<Signal<T>>) -> AudioStream<T> {
         let time = Arc::new(AtomicI64::new(0));
          let tx = Mutex::new(AudioTxGuard{
             tx: topic.get_publisher(),
+            space: topic.space.clone(),
             time: time.clone(),
+            seq: Cell::new(0),
+            pending: Cell::new(None),
         });
         AudioStream { tx, topic, time }
         
     }
-    pub fn get_subscriber(&self) -> Receiver<Arc<Signal<T>>> {
+    pub fn get_subscriber(&self) -> Subscriber<Signal<T>> {
         self.topic.get_subscriber()
     }
+    pub fn get_bounded_subscriber(&self, capacity: usize, overflow: Overflow) -> Subscriber<Signal<T>> {
+        self.topic.get_bounded_subscriber(capacity, overflow)
+    }
+    /// Unbounded subscriber as a `futures::Stream`. Use `Subscriber::into_async` for the other kinds.
+    pub fn get_async_subscriber(&self) -> AsyncSubscriber<Signal<T>> {
+        self.get_subscriber().into_async()
+    }
+    /// Subscriber that starts with the retained history, see `with_history`
+    pub fn get_subscriber_with_history(&self) -> Subscriber<Signal<T>> {
+        self.topic.get_subscriber_with_history()
+    }
+    /// Retained chunks, oldest first
+    pub fn history(&self) -> Vec<Arc<Signal<T>>> {
+        self.topic.history()
+    }
+    /// Stream time window `[start, end)` from the history, e.g. the samples before a trigger.
+    /// Gaps are zero-filled. Fails unless the window lies within the retained audio.
+    pub fn fetch(&self, start: i64, end: i64) -> Result<Signal<T>> {
+        let history = self.history();
+        let (Some(first), Some(last)) = (history.first(), history.last()) else {
+            return Err(anyhow!("mulink-dsp::audiostream_no_history"));
+        };
+        if start < first.time || end > last.end_time() || start > end {
+            return Err(anyhow!("mulink-dsp::audiostream_history_range: [{start}, {end}) not within [{}, {})", first.time, last.end_time()));
+        }
+        let mut out = Signal::from_vec(last.sample_rate, vec![num::Complex::<T>::zero(); (end - start) as usize]);
+        out.time = start;
+        out.meta = last.meta.clone();
+        for chunk in history.iter().filter(|x| x.time < end && x.end_time() > start) {
+            let mut part = chunk.slice_time(start, end);
+            let offset = (part.time - start) as usize;
+            out[offset..offset + part.len()].copy_from_slice(&part);
+            out.tags.append(&mut part.tags);
+        }
+        Ok(out)
+    }
+    /// `send` that waits asynchronously instead of blocking the thread while a bounded stream
+    /// (see `with_capacity`) is full. The transmit lock is only held while trying, never across
+    /// an await, so the future can move between threads. Dropping it before it completes sends
+    /// nothing and leaves the stream time as it was.
+    pub async fn send_async(&self, sig: Signal<T>) -> anyhow::Result<()> {
+        let mut sig = Some(sig);
+        futures::future::poll_fn(|cx| {
+            // Register before the second attempt so room made in between is not missed
+            for attempt in 0..2 {
+                let tx = self.lock()?;
+                sig = tx.try_send(sig.take().expect("polled after completion"))?;
+                if sig.is_none() {
+                    return Poll::Ready(Ok(()));
+                }
+                if attempt == 0 {
+                    tx.space.register(cx.waker());
+                }
+            }
+            Poll::Pending
+        }).await
+    }
     pub fn lock(&self) -> anyhow::Result<MutexGuard<'_, AudioTxGuard<T>>> {
         if let Ok(tx) = self.tx.lock() {
             Ok(tx)
@@ -108,10 +565,21 @@ impl<T: SignalType> AudioStream<T> {
     pub fn time(&self) -> i64 {
         self.time.load(std::sync::atomic::Ordering::Relaxed)
     }
+    /// Deliver the chunks already sent, end the subscribers' streams and join the dispatch thread.
+    /// Further `send`s fail.
+    pub fn close(&self) -> Result<()> {
+        self.topic.close()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.topic.is_closed()
+    }
 }
 
 #[test]
 fn test_audiostream() -> anyhow::Result<()> {
+    use futures::{executor::block_on, FutureExt, StreamExt};
+    use crate::core::block::{filter::Filter, refragment::Refragmenter};
+
     init_tracing();
     info!("Unit test: test_audiostream");
     // Create a new AudioStream (yields chunks of Complex<f32>)
@@ -157,5 +625,304 @@ fn test_audiostream() -> anyhow::Result<()> {
     trace!("time: {}", stream.time());
     assert_eq!(stream.time(), 512);
 
+    // Bounded subscribers: drop policies and backpressure
+    let topic = Topic::<usize>::new();
+    let newest = topic.get_bounded_subscriber(2, Overflow::DropNewest);
+    let oldest = topic.get_bounded_subscriber(2, Overflow::DropOldest);
+    let unbounded = topic.get_subscriber();
+    let publisher = topic.get_publisher();
+    for msg in 0..5 {
+        publisher.send(msg)?;
+    }
+    // Everything has been dispatched once the unbounded subscriber saw the last message
+    let received = (0..5).map(|_| unbounded.recv().map(|x| *x)).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(received, vec![0, 1, 2, 3, 4]);
+
+    assert_eq!((newest.dropped(), *newest.recv()?, *newest.recv()?), (3, 0, 1));
+    assert_eq!((oldest.dropped(), *oldest.recv()?, *oldest.recv()?), (3, 3, 4));
+    assert!(newest.try_recv().is_err() && oldest.try_recv().is_err());
+
+    // A blocking subscriber holds back the publisher once the topic queue is full too
+    let stream = AudioStream::<f32>::with_capacity(1);
+    let slow = stream.get_bounded_subscriber(1, Overflow::Block);
+    // One chunk queued at the subscriber, one held by the daemon, one in the topic queue
+    for _ in 0..3 {
+        block_on(stream.send_async(Signal::from_vec(8000.0, vec![0.0_f32; 16])))?;
+    }
+    // The fourth cannot go anywhere until the subscriber receives
+    assert!(stream.send_async(Signal::from_vec(8000.0, vec![0.0_f32; 16])).now_or_never().is_none());
+    let producer = {
+        let stream = Arc::new(stream);
+        thread::spawn(move || -> anyhow::Result<()> {
+            for _ in 3..10 {
+                stream.lock()?.send(Signal::from_vec(8000.0, vec![0.0_f32; 16]))?;
+            }
+            Ok(())
+        })
+    };
+    let times = (0..10).map(|_| slow.recv().map(|x| x.time)).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(times, (0..10).map(|x| x * 16).collect::<Vec<_>>());
+    producer.join().unwrap()?;
+    assert_eq!(slow.dropped(), 0);
+
+    // Dropping a blocked subscriber releases the daemon
+    let topic = Topic::<usize>::with_history(|msgs| msgs.truncate(1));
+    let live = topic.get_subscriber();
+    let stalled = topic.get_bounded_subscriber(1, Overflow::Block);
+    let publisher = topic.get_publisher();
+    for msg in 0..3 {
+        publisher.send(msg)?;
+    }
+    // `live` is served first, so once it has 1 the daemon is waiting on `stalled` to take 1
+    assert_eq!((0..2).map(|_| live.recv().map(|x| *x)).collect::<Result<Vec<_>, _>>()?, vec![0, 1]);
+    // Subscribing and reading the history do not wait for it
+    let late = topic.get_subscriber();
+    assert_eq!(topic.history().len(), 1);
+    drop(stalled);
+    assert_eq!(*live.recv()?, 2);
+    assert_eq!(*late.recv()?, 2);
+
+    // Shutdown
+    let stream = AudioStream::<f32>::new();
+    let sub = stream.get_subscriber();
+    for _ in 0..4 {
+        stream.lock()?.send(Signal::from_vec(8000.0, vec![0.0_f32; 32]))?;
+    }
+    stream.close()?;
+    assert!(stream.is_closed());
+
+    // Everything sent before the close is delivered, then the stream ends
+    assert!(!sub.is_finished());
+    let times = sub.iter().map(|x| x.time).collect::<Vec<_>>();
+    assert_eq!(times, vec![0, 32, 64, 96]);
+    assert!(sub.is_finished());
+    assert!(sub.recv().is_err());
+
+    // Sending and subscribing after the close
+    assert!(stream.lock()?.send(Signal::from_vec(8000.0, vec![0.0_f32; 32])).is_err());
+    {
+        // A failed `send_at` neither re-times the stream nor uses up the pending gap
+        let tx = stream.lock()?;
+        tx.overrun(8);
+        assert!(tx.send_at(Signal::from_vec(8000.0, vec![0.0_f32; 32]), 1000).is_err());
+        assert_eq!((stream.time(), tx.pending.get()), (136, Some(Discontinuity::Gap { samples: 8 })));
+    }
+    let late = stream.get_subscriber();
+    assert!(late.is_finished() && late.recv().is_err());
+    stream.close()?;
+
+    // Dropping a topic drains and stops its daemon too
+    let topic = Topic::<usize>::new();
+    let sub = topic.get_subscriber();
+    let publisher = topic.get_publisher();
+    publisher.send(7)?;
+    drop(topic);
+    assert_eq!(sub.iter().map(|x| *x).collect::<Vec<_>>(), vec![7]);
+    assert!(publisher.send(8).is_err());
+
+    // Gaps and resets
+    let stream = AudioStream::<f64>::new();
+    let sub = stream.get_subscriber();
+    let lossy = stream.get_bounded_subscriber(1, Overflow::DropNewest);
+    let chunk = |value: f64| Signal::from_vec(8000.0, vec![value; 100]);
+    {
+        let tx = stream.lock()?;
+        tx.send(chunk(1.0))?;
+        tx.overrun(50);
+        tx.send(chunk(2.0))?;
+        tx.send_at(chunk(3.0), 400)?;
+        tx.send_at(chunk(4.0), 0)?;
+        tx.reset();
+        tx.send(chunk(5.0))?;
+    }
+    stream.close()?;
+
+    let received = sub.iter().map(Arc::unwrap_or_clone).collect::<Vec<_>>();
+    let markers = received.iter().map(|x| (x.stream.unwrap().seq, x.time, x.stream.unwrap().discontinuity)).collect::<Vec<_>>();
+    assert_eq!(markers, vec![
+        (0, 0, None),
+        (1, 150, Some(Discontinuity::Gap { samples: 50 })),
+        (2, 400, Some(Discontinuity::Gap { samples: 150 })),
+        (3, 0, Some(Discontinuity::Reset)),
+        (4, 100, Some(Discontinuity::Reset)),
+    ]);
+
+    // The monitor agrees with the producer's markers on a complete stream
+    let mut monitor = StreamMonitor::new();
+    assert!(received.iter().zip(markers.iter()).all(|(x, marker)| monitor.check(x) == marker.2));
+
+    // The lossy subscriber only kept the first chunk. Missed chunks show up as a gap, or a reset if time went backwards.
+    let kept = lossy.iter().collect::<Vec<_>>();
+    assert_eq!((kept.len(), lossy.dropped()), (1, 4));
+    let mut monitor = StreamMonitor::new();
+    let mut late = received[2].clone();
+    assert_eq!((monitor.check(&received[0]), monitor.check(&late)), (None, Some(Discontinuity::Gap { samples: 300 })));
+    late.stream.as_mut().unwrap().seq = 7;
+    late.time = 0;
+    assert_eq!(monitor.check(&late), Some(Discontinuity::Reset));
+
+    // Refragmenter zero-fills short gaps and drops partial fragments on reset
+    let mut refrag = Refragmenter::<f64>::new(8000.0, 64);
+    let frags = received.iter().flat_map(|x| {
+        refrag.push(&mut x.clone());
+        (&mut refrag).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+    let samples = frags[..3].iter().flat_map(|x| x.to_vec()).map(|x| x.re).collect::<Vec<_>>();
+    assert_eq!(samples, [vec![1.0; 100], vec![0.0; 50], vec![2.0; 42]].concat());
+    // A gap longer than a fragment drops the 58 pending samples and skips ahead instead of filling
+    assert_eq!(frags.iter().map(|x| x.time).collect::<Vec<_>>(), vec![0, 64, 128, 400, 464, 528]);
+    assert!((3..6).all(|idx| frags[idx].iter().all(|x| x.re == idx as f64)));
+
+    // Filter output across a gap shorter than its FFT equals filtering the zero-filled signal
+    let kernel = Signal::from_vec(8000.0, vec![0.0625_f64; 16]);
+    let mut filter = Filter::<f64>::new(kernel.clone())?;
+    let mut out = filter.process(received[0].clone()).unwrap_or(Signal::new(8000.0));
+    out.append(&mut filter.process(received[1].clone()).unwrap_or(Signal::new(8000.0)));
+    out.append(&mut filter.finish().unwrap());
+    let mut filled = chunk(1.0);
+    filled.append(&mut vec![num::Complex::new(0.0, 0.0); 50]);
+    filled.append(&mut chunk(2.0));
+    let reference = Filter::<f64>::new(kernel.clone())?.process_and_finish(filled).unwrap();
+    assert_eq!(out.len(), reference.len());
+    assert!(out.iter().zip(reference.iter()).all(|(a, b)| (a - b).norm() < 1e-9));
+
+    // A gap longer than the FFT restarts the filter after it rather than filtering zeros
+    let long = |value: f64| Signal::from_vec(8000.0, vec![value; 300]);
+    let mut filter = Filter::<f64>::new(kernel.clone())?;
+    filter.process(long(1.0));
+    let mut after = long(3.0);
+    after.stream = Some(StreamInfo { seq: 1, discontinuity: Some(Discontinuity::Gap { samples: 1_000_000 }) });
+    let out = filter.process(after).unwrap();
+    let reference = Filter::<f64>::new(kernel)?.process(long(3.0)).unwrap();
+    assert_eq!((out.time, out.len()), (reference.time + 1_000_300, reference.len()));
+    assert!(out.iter().zip(reference.iter()).all(|(a, b)| (a - b).norm() < 1e-9));
+
+    // History
+    let stream = AudioStream::<f64>::with_history(0.1);
+    let live = stream.get_subscriber();
+    let chunk = |value: f64| Signal::from_vec(1000.0, vec![value; 40]);
+    for idx in 0..10 {
+        stream.lock()?.send(chunk(idx as f64))?;
+    }
+    // Wait for dispatch, history is updated before delivery
+    let received = (0..10).map(|_| live.recv()).collect::<Result<Vec<_>, _>>()?;
+    // 100 samples are kept, which takes the last three chunks
+    assert_eq!(stream.history().iter().map(|x| x.time).collect::<Vec<_>>(), vec![280, 320, 360]);
+
+    // Pre-trigger window: a detector that fired at the start of the last chunk asks for the 50 samples before it
+    let trigger = received[9].time;
+    let pre = stream.fetch(trigger - 50, trigger + 10)?;
+    assert_eq!((pre.time, pre.len()), (310, 60));
+    assert_eq!(pre.iter().map(|x| x.re).collect::<Vec<_>>(), [vec![7.0; 10], vec![8.0; 40], vec![9.0; 10]].concat());
+    assert!(stream.fetch(100, 200).is_err() && stream.fetch(350, 500).is_err());
+
+    // A late subscriber starts with the history and continues without duplicates
+    let late = stream.get_subscriber_with_history();
+    {
+        let tx = stream.lock()?;
+        tx.overrun(20);
+        tx.send(chunk(10.0))?;
+    }
+    stream.close()?;
+    let mut monitor = StreamMonitor::new();
+    let replayed = late.iter().map(|x| (x.time, monitor.check(&x))).collect::<Vec<_>>();
+    assert_eq!(replayed, vec![(280, None), (320, None), (360, None), (420, Some(Discontinuity::Gap { samples: 20 }))]);
+    // The gap is zero-filled, and history stays readable after close
+    let across = stream.fetch(390, 430)?;
+    assert_eq!(across.iter().map(|x| x.re).collect::<Vec<_>>(), [vec![9.0; 10], vec![0.0; 20], vec![10.0; 10]].concat());
+
+    // A reset drops the older audio, and plain streams keep nothing
+    let stream = AudioStream::<f64>::with_history(1.0);
+    let sub = stream.get_subscriber();
+    stream.lock()?.send(chunk(1.0))?;
+    stream.lock()?.send_at(chunk(2.0), 0)?;
+    stream.close()?;
+    assert_eq!(sub.iter().count(), 2);
+    assert_eq!(stream.history().iter().map(|x| x[0].re).collect::<Vec<_>>(), vec![2.0]);
+    assert!(AudioStream::<f64>::new().fetch(0, 0).is_err());
+
+    // Async producer and consumer
+    // Producer thread, consumer task
+    let stream = Arc::new(AudioStream::<f32>::new());
+    let sub = stream.get_async_subscriber();
+    // Each chunk is sent once the previous one arrived, so the task has to wait for every one
+    let (ack, acked) = crossbeam::channel::unbounded::<()>();
+    let producer = {
+        let stream = stream.clone();
+        thread::spawn(move || -> anyhow::Result<()> {
+            for idx in 0..5 {
+                if idx > 0 {
+                    acked.recv()?;
+                }
+                stream.lock()?.send(Signal::from_vec(8000.0, vec![0.0_f32; 16]))?;
+            }
+            stream.close()
+        })
+    };
+    let times = block_on(sub.map(|x| {
+        let _ = ack.send(());
+        x.time
+    }).collect::<Vec<_>>());
+    producer.join().unwrap()?;
+    assert_eq!(times, vec![0, 16, 32, 48, 64]);
+
+    // Producer and consumer on one task through a full bounded stream: neither may block the thread
+    let stream = AudioStream::<f32>::with_capacity(1);
+    let mut sub = stream.get_bounded_subscriber(1, Overflow::Block).into_async();
+    let (sent, received) = block_on(async {
+        let send = async {
+            for _ in 0..20 {
+                stream.send_async(Signal::from_vec(8000.0, vec![0.0_f32; 16])).await?;
+                // Let the consumer run. `pending!` alone would not wake the task again once the
+                // consumer has caught up and nothing else is in flight.
+                let mut yielded = false;
+                futures::future::poll_fn(|cx| {
+                    if std::mem::replace(&mut yielded, true) {
+                        return Poll::Ready(());
+                    }
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }).await;
+            }
+            anyhow::Ok(20)
+        };
+        let recv = (&mut sub).take(20).map(|x| x.time).collect::<Vec<_>>();
+        futures::join!(send, recv)
+    });
+    assert_eq!(sent?, 20);
+    assert_eq!(received, (0..20).map(|x| x * 16).collect::<Vec<_>>());
+    // `close` joins the dispatch thread, so it is not called from inside the task
+    stream.close()?;
+    assert!(block_on(sub.next()).is_none());
+    assert!(sub.is_finished() && sub.dropped() == 0);
+
+    // Sending into a closed stream fails, without moving the stream on
+    let time = stream.time();
+    assert!(block_on(stream.send_async(Signal::from_vec(8000.0, vec![0.0_f32; 16]))).is_err());
+    assert_eq!(stream.time(), time);
+
+    // A send that is dropped while waiting for room leaves no gap behind
+    let stream = Arc::new(AudioStream::<f32>::with_capacity(1));
+    let sub = stream.get_bounded_subscriber(1, Overflow::Block);
+    let chunk = || Signal::from_vec(8000.0, vec![0.0_f32; 16]);
+    // Fill the subscriber, the dispatcher and the channel
+    for _ in 0..3 {
+        block_on(stream.send_async(chunk()))?;
+    }
+    let mut pending = Box::pin(stream.send_async(chunk()));
+    assert!((&mut pending).now_or_never().is_none());
+    drop(pending);
+    assert_eq!(stream.time(), 48);
+    // The future can be spawned onto a multi-threaded executor
+    fn assert_send<F: Send>(_: &F) {}
+    let task = {
+        let stream = stream.clone();
+        async move { stream.send_async(chunk()).await }
+    };
+    assert_send(&task);
+    let consumer = thread::spawn(move || sub.iter().map(|x| (x.time, x.stream.unwrap().discontinuity)).collect::<Vec<_>>());
+    block_on(task)?;
+    stream.close()?;
+    assert_eq!(consumer.join().unwrap(), vec![(0, None), (16, None), (32, None), (48, None)]);
     Ok(())
 }
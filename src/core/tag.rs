@@ -0,0 +1,132 @@
+use crate::prelude::*;
+
+// Tags mark events at sample indices. `Tag::time` is on the same axis as `Signal::time`, so a tag
+// stays valid when a signal is sliced or re-chunked, and blocks only need to re-time it when they
+// change the time axis (delay compensation, resampling, their own sample counters).
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagValue {
+    Flag,
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tag {
+    /// Stream time of the tagged sample
+    pub time: i64,
+    pub key: String,
+    pub value: TagValue,
+}
+
+impl<T: SignalType> Signal<T> {
+    /// Tag sample `idx` of this signal
+    pub fn add_tag(&mut self, idx: usize, key: &str, value: TagValue) {
+        let time = self.time + idx as i64;
+        let pos = self.tags.partition_point(|tag| tag.time <= time);
+        self.tags.insert(pos, Tag { time, key: key.to_string(), value });
+    }
+    pub fn with_tag(mut self, idx: usize, key: &str, value: TagValue) -> Signal<T> {
+        self.add_tag(idx, key, value);
+        self
+    }
+    /// Tags named `key`
+    pub fn find_tags<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a Tag> + 'a {
+        self.tags.iter().filter(move |tag| tag.key == key)
+    }
+    /// Move the signal (and its tags) to start at `time`
+    pub fn retime(&mut self, time: i64) {
+        let shift = time - self.time;
+        self.tags.iter_mut().for_each(|tag| tag.time += shift);
+        self.time = time;
+    }
+}
+
+/// Tags waiting inside a block for the output that covers them
+#[derive(Clone, Debug, Default)]
+pub struct TagBuffer {
+    pending: Vec<Tag>,
+}
+impl TagBuffer {
+    pub fn new() -> TagBuffer {
+        TagBuffer::default()
+    }
+    /// Queue the tags of `data`, `map` converts an input time to the block's output time
+    pub fn push<T: SignalType>(&mut self, data: &Signal<T>, map: impl Fn(i64) -> i64) {
+        self.pending.extend(data.tags.iter().map(|tag| Tag { time: map(tag.time), ..tag.clone() }));
+        self.pending.sort_by_key(|tag| tag.time);
+    }
+    /// Move the queued tags that fall before the end of `out` onto it
+    pub fn attach<T: SignalType>(&mut self, out: &mut Signal<T>) {
+        let end = out.end_time();
+        let count = self.pending.partition_point(|tag| tag.time < end);
+        out.tags.extend(self.pending.drain(..count));
+    }
+    /// End-of-stream, everything left goes onto `out`
+    pub fn attach_all<T: SignalType>(&mut self, out: &mut Signal<T>) {
+        out.tags.append(&mut self.pending);
+    }
+    /// Drop queued tags at or after `time`, e.g. for samples discarded on a reset
+    pub fn discard_from(&mut self, time: i64) {
+        self.pending.retain(|tag| tag.time < time);
+    }
+}
+
+#[test]
+fn test_tags() -> anyhow::Result<()> {
+    use crate::core::{block::{filter::Filter, refragment::Refragmenter, resample::Resampler}, graph::{Block, Graph}, signal::FromFunction, stream::AudioStream};
+
+    let detect = |sig: &Signal<f64>| sig.find_tags("detect").map(|tag| tag.time).collect::<Vec<_>>();
+    let mut sig = Signal::from_function(48000.0, 4800, |x| x).with_tag(1000, "detect", TagValue::Int(1)).with_tag(3000, "detect", TagValue::Int(2));
+    sig.add_tag(2000, "gain", TagValue::Float(6.0));
+    sig.retime(500);
+    assert_eq!(detect(&sig), vec![1500, 3500]);
+
+    // Slicing and chunking keep tags on their sample
+    let chunks = sig.split_chunks(480);
+    assert_eq!(detect(&chunks[2]), vec![1500]);
+    assert_eq!(chunks.iter().map(|x| x.tags.len()).sum::<usize>(), 3);
+    assert_eq!(Signal::concat_all(&chunks)?.tags, sig.tags);
+    assert!(sig.slice(0..1000).tags.is_empty());
+
+    // The filter output is delay compensated, so tags keep their sample index (on the filter's own axis from 0)
+    let mut filter = Filter::<f64>::lowpass(8000.0, 64, 48000.0)?;
+    let mut filtered = chunks.iter().flat_map(|x| filter.process(x.clone())).collect::<Vec<_>>();
+    filtered.extend(filter.flush());
+    let filtered = filtered.iter().fold(Vec::new(), |mut acc, x| { acc.extend(detect(x).into_iter().map(|t| (t, x.time, x.end_time()))); acc });
+    assert_eq!(filtered.iter().map(|x| x.0).collect::<Vec<_>>(), vec![1000, 3000]);
+    assert!(filtered.iter().all(|(t, start, end)| start <= t && t < end));
+
+    // Resampling scales the tag times
+    let mut resampler = Resampler::<f64>::new(48000.0, 2, 3, 24)?;
+    let mut resampled = chunks.iter().map(|x| resampler.process(x.clone())).collect::<Vec<_>>();
+    resampled.push(resampler.flush());
+    let times = resampled.iter().flat_map(detect).collect::<Vec<_>>();
+    assert_eq!(times, vec![1000, 2333]);
+    assert!(resampled.iter().all(|x| x.tags.iter().all(|tag| x.time <= tag.time && tag.time < x.end_time())));
+    assert_eq!(sig.decimate(4).find_tags("gain").next().unwrap().time, 625);
+
+    // Through an AudioStream (which restamps time) and graph workers: a refragmenter, and a
+    // filter whose delay compensation must survive the output stream
+    let source = AudioStream::<f64>::new();
+    let mut graph = Graph::new();
+    let out = graph.add(&source, Refragmenter::<f64>::new(48000.0, 1000));
+    let filtered = graph.add(&source, Filter::<f64>::lowpass(8000.0, 64, 48000.0)?);
+    let sub = out.get_subscriber();
+    let filtered_sub = filtered.get_subscriber();
+    for chunk in chunks {
+        source.lock()?.send(chunk)?;
+    }
+    source.close()?;
+    let received = sub.iter().collect::<Vec<_>>();
+    let filtered = filtered_sub.iter().collect::<Vec<_>>();
+    graph.join()?;
+    let tags = received.iter().flat_map(|x| x.tags.iter().map(|tag| (tag.time, tag.key.clone(), x.time))).collect::<Vec<_>>();
+    assert_eq!(tags, vec![(1000, "detect".to_string(), 1000), (2000, "gain".to_string(), 2000), (3000, "detect".to_string(), 3000)]);
+    let detected = filtered.iter().flat_map(|x| detect(x).into_iter().map(|t| (t, x.time, x.end_time()))).collect::<Vec<_>>();
+    assert_eq!(detected.iter().map(|x| x.0).collect::<Vec<_>>(), vec![1000, 3000]);
+    assert!(detected.iter().all(|(t, start, end)| start <= t && t < end));
+    assert_eq!(Block::<f64>::flush(&mut Refragmenter::<f64>::new(48000.0, 10))?.len(), 0);
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use anyhow::anyhow;
+
+use crate::{core::signal::{Amplitude, SignalMeta, Unit}, prelude::*};
+
+/// What 0 dB means. Power quantities use the same references via `Amplitude::from_power`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DbReference {
+    /// dB re 1 µPa (underwater acoustics)
+    MicroPascal,
+    /// dB re 1 V
+    Volt,
+    /// dB re a sample value of 1.0 (dBFS, RMS based)
+    FullScale,
+    Custom { unit: Unit, value: f64 },
+}
+impl DbReference {
+    pub fn unit(&self) -> Unit {
+        match self {
+            DbReference::MicroPascal => Unit::Pascals,
+            DbReference::Volt => Unit::Volts,
+            DbReference::FullScale => Unit::Counts,
+            DbReference::Custom { unit, .. } => unit.clone(),
+        }
+    }
+    /// Reference value in `unit()`
+    pub fn value(&self) -> f64 {
+        match self {
+            DbReference::MicroPascal => 1e-6,
+            DbReference::Volt | DbReference::FullScale => 1.0,
+            DbReference::Custom { value, .. } => *value,
+        }
+    }
+}
+impl std::fmt::Display for DbReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbReference::MicroPascal => write!(f, "dB re 1 µPa"),
+            DbReference::Volt => write!(f, "dBV"),
+            DbReference::FullScale => write!(f, "dBFS"),
+            DbReference::Custom { unit, value } => write!(f, "dB re {value} {unit}"),
+        }
+    }
+}
+
+/// A level in dB against a physical reference
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+    pub db: f64,
+    pub reference: DbReference,
+}
+impl Level {
+    /// `amplitude` is in `reference.unit()`
+    pub fn new(amplitude: Amplitude, reference: DbReference) -> Level {
+        Level { db: amplitude.db() - 20.0 * reference.value().log10(), reference }
+    }
+    /// Linear amplitude in `reference.unit()`
+    pub fn amplitude(&self) -> Amplitude {
+        Amplitude::Log(self.db + 20.0 * self.reference.value().log10()).to_linear()
+    }
+}
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.1} {}", self.db, self.reference)
+    }
+}
+
+/// Hydrophone -> gain stages -> ADC. Describes how a recorded sample value maps to pressure.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalibrationChain {
+    /// Open circuit sensitivity in dB re 1 V/µPa (e.g. -170)
+    pub hydrophone_sensitivity: f64,
+    /// Preamp / amplifier gains in dB
+    pub gains: Vec<f64>,
+    /// Volts at the ADC input for a sample value of 1.0
+    pub adc_full_scale: f64,
+}
+impl CalibrationChain {
+    pub fn total_gain(&self) -> Amplitude {
+        Amplitude::Log(self.gains.iter().sum())
+    }
+    /// Volts at the hydrophone terminals per sample value
+    pub fn volts_per_count(&self) -> f64 {
+        self.adc_full_scale / self.total_gain().linear::<f64>()
+    }
+    pub fn pascals_per_count(&self) -> f64 {
+        let volts_per_micropascal = Amplitude::Log(self.hydrophone_sensitivity).linear::<f64>();
+        self.volts_per_count() / volts_per_micropascal * 1e-6
+    }
+    /// Metadata describing samples in counts from this chain, in Pascals
+    pub fn meta(&self) -> SignalMeta {
+        self.calibrate_meta(SignalMeta::default()).expect("default metadata is in counts")
+    }
+    /// `meta` of samples in counts, re-expressed in Pascals. A gain already on the counts (e.g.
+    /// digital gain) is kept on top of the chain's. Fails for samples in any other unit, which
+    /// this chain does not describe.
+    pub fn calibrate_meta(&self, meta: SignalMeta) -> anyhow::Result<SignalMeta> {
+        if meta.unit != Unit::Counts {
+            return Err(anyhow!("mulink-dsp::calibration_unit: expected samples in {}, got {}", Unit::Counts, meta.unit));
+        }
+        Ok(SignalMeta { unit: Unit::Pascals, gain: meta.gain * self.pascals_per_count(), ..meta })
+    }
+    /// Attach the calibration to `signal` without touching the samples, see `calibrate_meta`
+    pub fn calibrate<T: SignalType>(&self, mut signal: Signal<T>) -> anyhow::Result<Signal<T>> {
+        signal.meta = Some(self.calibrate_meta(signal.meta.take().unwrap_or_default())?);
+        Ok(signal)
+    }
+}
+
+impl<T: SignalType> Signal<T> {
+    /// Samples scaled by the metadata gain, so that a sample value is in `meta.unit`
+    pub fn to_physical(&self) -> Signal<T> {
+        let mut out = self.clone();
+        let Some(mut meta) = out.meta.take() else {
+            return out;
+        };
+        let gain = T::from_f64(meta.gain).unwrap();
+        out.iter_mut().for_each(|x| *x *= gain);
+        meta.gain = 1.0;
+        out.with_meta(meta)
+    }
+    /// RMS level against `reference`. The signal unit (`Counts` without metadata) must match the reference.
+    pub fn level(&self, reference: DbReference) -> anyhow::Result<Level> {
+        let meta = self.meta.clone().unwrap_or_default();
+        if meta.unit != reference.unit() {
+            return Err(anyhow!("Cannot express a signal in {} as {reference}", meta.unit));
+        }
+        Ok(Level::new(Amplitude::Linear(self.rms().linear::<f64>() * meta.gain), reference))
+    }
+}
+
+#[test]
+fn test_units() -> anyhow::Result<()> {
+    use std::f64::consts::PI;
+    use crate::core::signal::FromFunction;
+
+    // -170 dB re 1 V/µPa hydrophone, 20 dB preamp, 2.5 V full scale ADC
+    let chain = CalibrationChain { hydrophone_sensitivity: -170.0, gains: vec![20.0], adc_full_scale: 2.5 };
+    assert!((chain.volts_per_count() - 0.25).abs() < 1e-12);
+
+    // RMS of 0.1 counts -> 0.025 V at the hydrophone -> 7.9e6 µPa
+    let sig = Signal::from_function(48000.0, 48000, |x| f64::sqrt(2.0) * 0.1 * f64::sin(1000.0 * 2.0 * PI * x));
+    let counts = sig.level(DbReference::FullScale)?;
+    assert!((counts.db + 20.0).abs() < 1e-6);
+    assert!(sig.level(DbReference::MicroPascal).is_err());
+
+    let calibrated = chain.calibrate(sig.clone())?;
+    assert_eq!(calibrated.meta, Some(chain.meta()));
+    let spl = calibrated.level(DbReference::MicroPascal)?;
+    assert!((spl.db - 20.0 * f64::log10(0.025 / 10f64.powf(-170.0 / 20.0))).abs() < 1e-6);
+    assert_eq!(spl.to_string(), "138.0 dB re 1 µPa");
+    assert!((spl.amplitude().linear::<f64>() - 0.025 / 10f64.powf(-170.0 / 20.0) * 1e-6).abs() < 1e-9);
+
+    // Scaling into Pascals keeps the level
+    let physical = calibrated.to_physical();
+    assert!((physical.level(DbReference::MicroPascal)?.db - spl.db).abs() < 1e-9);
+    assert!((physical.rms().linear::<f64>() - chain.pascals_per_count() * 0.1).abs() < 1e-9);
+
+    // Calibrating twice or calibrating volts is an error, a gain on the counts is kept
+    assert!(chain.calibrate(calibrated.clone()).is_err());
+    assert!(chain.calibrate(sig.clone().with_meta(SignalMeta { unit: Unit::Volts, ..Default::default() })).is_err());
+    let digital = chain.calibrate(sig.with_meta(SignalMeta { gain: 0.5, ..Default::default() }))?;
+    assert!((digital.meta.unwrap().gain - 0.5 * chain.pascals_per_count()).abs() < 1e-18);
+
+    // Power quantities share the references
+    let power = Level::new(Amplitude::from_power(1e-6), DbReference::Volt);
+    assert!((power.db + 60.0).abs() < 1e-9);
+    Ok(())
+}
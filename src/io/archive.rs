@@ -0,0 +1,421 @@
+use std::{fs::File, io::{BufWriter, Write}, marker::PhantomData, sync::atomic::{AtomicBool, Ordering}};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::DateTime;
+use itertools::Itertools;
+use memmap2::Mmap;
+use num::Complex;
+use rkyv::{rancor, util::AlignedVec, Archive, Archived, Deserialize, Serialize};
+
+use crate::{core::signal::{SignalMeta, Unit}, prelude::*};
+
+// Indexed signal archive. Layout:
+//   [16 byte header: magic + sample size] [chunk]... [index] [16 byte footer: index offset, index size]
+// Every chunk and the index are rkyv roots starting on a 16 byte boundary, so a memory map of the
+// file can be accessed in place without decoding.
+
+const MAGIC: &[u8; 8] = b"MLKARCH1";
+const ALIGN: u64 = 16;
+
+/// On-disk form of `SignalMeta`
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MetaRecord {
+    /// Nanoseconds since the Unix epoch
+    pub epoch: Option<i64>,
+    pub center_frequency: Option<f64>,
+    pub unit: String,
+    pub gain: f64,
+}
+
+/// One archived `Signal` chunk, samples interleaved as `re, im`
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChunkRecord<T> {
+    pub sample_rate: f64,
+    pub time: i64,
+    pub meta: Option<MetaRecord>,
+    pub samples: Vec<T>,
+}
+
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IndexEntry {
+    pub time: i64,
+    pub len: u64,
+    pub offset: u64,
+    pub size: u64,
+}
+
+#[derive(Archive, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArchiveIndex {
+    pub sample_rate: f64,
+    /// Sorted by `time`, chunks do not overlap
+    pub entries: Vec<IndexEntry>,
+}
+
+/// Sample types that can be stored in an archive
+pub trait ArchiveSample: SignalType + Archive {
+    fn to_bytes(record: &ChunkRecord<Self>) -> Result<AlignedVec>;
+    fn access(bytes: &[u8]) -> Result<&ArchivedChunkRecord<Self>>;
+    /// # Safety
+    /// `bytes` must have passed `access` before
+    unsafe fn access_unchecked(bytes: &[u8]) -> &ArchivedChunkRecord<Self>;
+    fn from_archived(value: &Archived<Self>) -> Self;
+}
+macro_rules! archive_sample {
+    ($t:ty) => {
+        impl ArchiveSample for $t {
+            fn to_bytes(record: &ChunkRecord<Self>) -> Result<AlignedVec> {
+                Ok(rkyv::to_bytes::<rancor::Error>(record)?)
+            }
+            fn access(bytes: &[u8]) -> Result<&ArchivedChunkRecord<Self>> {
+                Ok(rkyv::access::<ArchivedChunkRecord<Self>, rancor::Error>(bytes)?)
+            }
+            unsafe fn access_unchecked(bytes: &[u8]) -> &ArchivedChunkRecord<Self> {
+                unsafe { rkyv::access_unchecked::<ArchivedChunkRecord<Self>>(bytes) }
+            }
+            fn from_archived(value: &Archived<Self>) -> Self {
+                value.to_native()
+            }
+        }
+    };
+}
+archive_sample!(f32);
+archive_sample!(f64);
+
+impl From<&SignalMeta> for MetaRecord {
+    fn from(meta: &SignalMeta) -> Self {
+        MetaRecord {
+            epoch: meta.epoch.and_then(|epoch| epoch.timestamp_nanos_opt()),
+            center_frequency: meta.center_frequency,
+            unit: meta.unit.to_string(),
+            gain: meta.gain,
+        }
+    }
+}
+impl ArchivedMetaRecord {
+    pub fn to_meta(&self) -> Result<SignalMeta> {
+        Ok(SignalMeta {
+            epoch: self.epoch.as_ref().map(|ns| DateTime::from_timestamp_nanos(ns.to_native())),
+            center_frequency: self.center_frequency.as_ref().map(|fc| fc.to_native()),
+            unit: self.unit.as_str().parse::<Unit>()?,
+            gain: self.gain.to_native(),
+        })
+    }
+}
+
+impl<T: ArchiveSample> ArchivedChunkRecord<T> {
+    pub fn len(&self) -> usize {
+        self.samples.len() / 2
+    }
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+    /// Interleaved `re, im` samples, borrowed from the archive
+    pub fn raw_samples(&self) -> &[Archived<T>] {
+        self.samples.as_slice()
+    }
+    pub fn sample(&self, idx: usize) -> Complex<T> {
+        Complex::new(T::from_archived(&self.samples[2 * idx]), T::from_archived(&self.samples[2 * idx + 1]))
+    }
+    /// Copy samples `range` (chunk relative) out of the archive
+    pub fn to_signal(&self, range: std::ops::Range<usize>) -> Result<Signal<T>> {
+        let range = range.start.min(self.len())..range.end.min(self.len());
+        let mut out = Signal::from_vec(self.sample_rate.to_native(), range.clone().map(|idx| self.sample(idx)).collect_vec());
+        out.time = self.time.to_native() + range.start as i64;
+        out.meta = self.meta.as_ref().map(|meta| meta.to_meta()).transpose()?;
+        Ok(out)
+    }
+}
+
+fn padding(pos: u64) -> usize {
+    ((ALIGN - pos % ALIGN) % ALIGN) as usize
+}
+
+/// Appends `Signal` chunks to an archive file. Chunks must share a sample rate and arrive in time order.
+pub struct ArchiveWriter<T: ArchiveSample> {
+    file: BufWriter<File>,
+    pos: u64,
+    index: ArchiveIndex,
+    _sample: PhantomData<T>,
+}
+impl<T: ArchiveSample> ArchiveWriter<T> {
+    pub fn create(path: &str, sample_rate: f64) -> Result<ArchiveWriter<T>> {
+        let mut file = BufWriter::new(File::create(path).with_context(|| format!("Failed to create archive {path}"))?);
+        let mut header = [0u8; ALIGN as usize];
+        header[..8].copy_from_slice(MAGIC);
+        header[8..12].copy_from_slice(&(size_of::<T>() as u32).to_le_bytes());
+        file.write_all(&header)?;
+        Ok(ArchiveWriter { file, pos: ALIGN, index: ArchiveIndex { sample_rate, entries: Vec::new() }, _sample: PhantomData })
+    }
+    fn write_aligned(&mut self, bytes: &[u8]) -> Result<u64> {
+        let pad = padding(self.pos);
+        self.file.write_all(&[0u8; ALIGN as usize][..pad])?;
+        let offset = self.pos + pad as u64;
+        self.file.write_all(bytes)?;
+        self.pos = offset + bytes.len() as u64;
+        Ok(offset)
+    }
+    pub fn write(&mut self, signal: &Signal<T>) -> Result<()> {
+        if signal.sample_rate != self.index.sample_rate {
+            return Err(anyhow!("Chunk sample rate {} does not match archive rate {}", signal.sample_rate, self.index.sample_rate));
+        }
+        if let Some(last) = self.index.entries.last() {
+            if signal.time < last.time + last.len as i64 {
+                return Err(anyhow!("Chunk at {} overlaps the previous chunk ending at {}", signal.time, last.time + last.len as i64));
+            }
+        }
+        if signal.is_empty() {
+            return Ok(());
+        }
+        let record = ChunkRecord {
+            sample_rate: signal.sample_rate,
+            time: signal.time,
+            meta: signal.meta.as_ref().map(MetaRecord::from),
+            samples: signal.iter().flat_map(|x| [x.re, x.im]).collect_vec(),
+        };
+        let bytes = T::to_bytes(&record)?;
+        let offset = self.write_aligned(&bytes)?;
+        self.index.entries.push(IndexEntry { time: signal.time, len: signal.len() as u64, offset, size: bytes.len() as u64 });
+        Ok(())
+    }
+    /// Write the index, the archive is unreadable until this is called
+    pub fn finish(mut self) -> Result<()> {
+        let bytes = rkyv::to_bytes::<rancor::Error>(&self.index)?;
+        let offset = self.write_aligned(&bytes)?;
+        let pad = padding(self.pos);
+        self.file.write_all(&[0u8; ALIGN as usize][..pad])?;
+        self.file.write_all(&offset.to_le_bytes())?;
+        self.file.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Memory-mapped, zero-copy view of an archive written by `ArchiveWriter`.
+/// The index is validated on open, each chunk the first time it is accessed.
+pub struct ArchiveReader<T: ArchiveSample> {
+    map: Mmap,
+    index: (usize, usize),
+    validated: Vec<AtomicBool>,
+    _sample: PhantomData<T>,
+}
+impl<T: ArchiveSample> ArchiveReader<T> {
+    pub fn open(path: &str) -> Result<ArchiveReader<T>> {
+        let file = File::open(path).with_context(|| format!("Failed to open archive {path}"))?;
+        // Safety: the archive must not be modified while mapped, as with any read-only data file
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < 2 * ALIGN as usize || &map[..8] != MAGIC {
+            return Err(anyhow!("{path} is not a signal archive"));
+        }
+        let sample_size = u32::from_le_bytes(map[8..12].try_into()?) as usize;
+        if sample_size != size_of::<T>() {
+            return Err(anyhow!("{path} stores {sample_size} byte samples, expected {}", size_of::<T>()));
+        }
+        let footer = map.len() - ALIGN as usize;
+        let offset = u64::from_le_bytes(map[footer..footer + 8].try_into()?) as usize;
+        let size = u64::from_le_bytes(map[footer + 8..footer + 16].try_into()?) as usize;
+        if offset.checked_add(size).is_none_or(|end| end > footer) {
+            return Err(anyhow!("{path} has a corrupt index"));
+        }
+        let index = rkyv::access::<ArchivedArchiveIndex, rancor::Error>(&map[offset..offset + size])?;
+        if let Some(idx) = index.entries.iter().position(|entry| entry.offset.to_native().checked_add(entry.size.to_native()).is_none_or(|end| end > offset as u64)) {
+            return Err(anyhow!("Chunk {idx} of {path} extends past the archive data"));
+        }
+        // Sorted and non-overlapping, so the time arithmetic in `chunk_at` and `read` cannot overflow
+        let mut prev_end = i64::MIN;
+        for (idx, entry) in index.entries.iter().enumerate() {
+            let time = entry.time.to_native();
+            match i64::try_from(entry.len.to_native()).ok().and_then(|len| time.checked_add(len)) {
+                Some(end) if time >= prev_end => prev_end = end,
+                _ => return Err(anyhow!("Chunk {idx} of {path} is out of order or overlaps the previous chunk")),
+            }
+        }
+        let validated = index.entries.iter().map(|_| AtomicBool::new(false)).collect_vec();
+        Ok(ArchiveReader { map, index: (offset, offset + size), validated, _sample: PhantomData })
+    }
+    pub fn index(&self) -> &ArchivedArchiveIndex {
+        // Safety: validated in `open`, and the map is read-only
+        unsafe { rkyv::access_unchecked::<ArchivedArchiveIndex>(&self.map[self.index.0..self.index.1]) }
+    }
+    fn entries(&self) -> &[ArchivedIndexEntry] {
+        self.index().entries.as_slice()
+    }
+    pub fn sample_rate(&self) -> f64 {
+        self.index().sample_rate.to_native()
+    }
+    /// Number of chunks
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+    /// First archived sample time
+    pub fn start_time(&self) -> Option<i64> {
+        Some(self.entries().first()?.time.to_native())
+    }
+    /// One past the last archived sample time
+    pub fn end_time(&self) -> Option<i64> {
+        let last = self.entries().last()?;
+        Some(last.time.to_native() + last.len.to_native() as i64)
+    }
+    /// Zero-copy access to chunk `idx`
+    pub fn chunk(&self, idx: usize) -> Result<&ArchivedChunkRecord<T>> {
+        let entry = self.entries().get(idx).ok_or(anyhow!("Chunk {idx} out of range"))?;
+        // Bounds checked against the index in `open`
+        let start = entry.offset.to_native() as usize;
+        let bytes = &self.map[start..start + entry.size.to_native() as usize];
+        if self.validated[idx].load(Ordering::Relaxed) {
+            // Safety: passed `access` on an earlier call, and the map is read-only
+            return Ok(unsafe { T::access_unchecked(bytes) });
+        }
+        let chunk = T::access(bytes)?;
+        if chunk.time != entry.time || chunk.len() as u64 != entry.len.to_native() || chunk.samples.len() % 2 != 0 || chunk.sample_rate != self.index().sample_rate {
+            return Err(anyhow!("Chunk {idx} does not match its index entry"));
+        }
+        self.validated[idx].store(true, Ordering::Relaxed);
+        Ok(chunk)
+    }
+    /// Index of the chunk containing sample `time`
+    pub fn chunk_at(&self, time: i64) -> Option<usize> {
+        let entries = self.entries();
+        let idx = entries.partition_point(|entry| entry.time.to_native() <= time).checked_sub(1)?;
+        let entry = &entries[idx];
+        (time < entry.time.to_native() + entry.len.to_native() as i64).then_some(idx)
+    }
+    /// Samples in `[start, end)`, gaps between chunks are zero-filled.
+    /// Metadata is taken from the first chunk touched.
+    pub fn read(&self, start: i64, end: i64) -> Result<Signal<T>> {
+        let mut out = Signal::new(self.sample_rate());
+        out.time = start;
+        let entries = self.entries();
+        let first = entries.partition_point(|entry| entry.time.to_native() + (entry.len.to_native() as i64) <= start);
+        for (idx, entry) in entries.iter().enumerate().skip(first) {
+            let chunk_start = entry.time.to_native();
+            if chunk_start >= end {
+                break;
+            }
+            let chunk = self.chunk(idx)?;
+            let from = usize::try_from(start.saturating_sub(chunk_start)).unwrap_or(0);
+            let to = usize::try_from(end.saturating_sub(chunk_start)).unwrap_or(0).min(chunk.len());
+            let part = chunk.to_signal(from..to.max(from))?;
+            if out.meta.is_none() {
+                out.meta = part.meta.clone();
+            }
+            let gap = part.time.checked_sub(out.end_time()).and_then(|gap| usize::try_from(gap).ok()).ok_or(anyhow!("Chunk {idx} overlaps the samples before it"))?;
+            let len = out.len();
+            out.resize(len + gap, Complex::default());
+            out.extend_from_slice(&part);
+        }
+        out.resize(usize::try_from(end.saturating_sub(start)).unwrap_or(0), Complex::default());
+        Ok(out)
+    }
+}
+
+impl<T: ArchiveSample> Signal<T> {
+    /// Archive a single signal, see `ArchiveWriter` for streams
+    pub fn write_archive(&self, path: &str) -> Result<()> {
+        let mut writer = ArchiveWriter::create(path, self.sample_rate)?;
+        writer.write(self)?;
+        writer.finish()
+    }
+    /// Everything in an archive as one signal
+    pub fn read_archive(path: &str) -> Result<Signal<T>> {
+        let reader = ArchiveReader::<T>::open(path)?;
+        match (reader.start_time(), reader.end_time()) {
+            (Some(start), Some(end)) => reader.read(start, end),
+            _ => Ok(Signal::new(reader.sample_rate())),
+        }
+    }
+}
+
+#[test]
+fn test_archive() -> anyhow::Result<()> {
+    use chrono::{TimeZone, Utc};
+    use crate::core::signal::FromFunction;
+
+    let path = std::env::temp_dir().join("mulink_test_archive.mla");
+    let path = path.to_str().unwrap();
+    let epoch = Utc.with_ymd_and_hms(2025, 5, 19, 12, 0, 0).unwrap();
+    let meta = SignalMeta { epoch: Some(epoch), center_frequency: Some(25000.0), unit: Unit::Pascals, gain: 0.5 };
+    let sig = Signal::from_function(48000.0, 10000, |x| Complex::new(x as f32, -x as f32)).with_meta(meta.clone());
+
+    // Chunked, with a gap of 100 samples after the third chunk
+    let mut writer = ArchiveWriter::<f32>::create(path, 48000.0)?;
+    for (idx, mut chunk) in sig.split_chunks(1000).into_iter().enumerate() {
+        if idx >= 3 {
+            chunk.time += 100;
+        }
+        writer.write(&chunk)?;
+    }
+    let mut late = Signal::from_vec(48000.0, vec![0.0_f32; 10]);
+    late.time = 0;
+    assert!(writer.write(&late).is_err());
+    writer.finish()?;
+
+    let reader = ArchiveReader::<f32>::open(path)?;
+    assert!(ArchiveReader::<f64>::open(path).is_err());
+    assert_eq!((reader.len(), reader.start_time(), reader.end_time()), (10, Some(0), Some(10100)));
+    assert_eq!(reader.chunk_at(2999), Some(2));
+    assert_eq!(reader.chunk_at(3050), None);
+    assert_eq!(reader.chunk_at(3100), Some(3));
+    assert_eq!(reader.chunk_at(10100), None);
+
+    // Zero-copy chunk access
+    let chunk = reader.chunk(5)?;
+    assert_eq!((chunk.time.to_native(), chunk.len()), (5100, 1000));
+    assert_eq!(chunk.sample(10), sig[5010]);
+    assert_eq!(chunk.meta.as_ref().unwrap().to_meta()?, meta);
+
+    // Random access by time across a chunk boundary and the gap
+    let window = reader.read(2950, 3150)?;
+    assert_eq!((window.time, window.len()), (2950, 200));
+    assert_eq!(window.meta, Some(meta));
+    assert_eq!(&window[..50], &sig[2950..3000]);
+    assert!(window[50..150].iter().all(|x| *x == Complex::default()));
+    assert_eq!(&window[150..], &sig[3000..3050]);
+
+    // A damaged chunk fails on access, every time, without affecting the others
+    let damaged = std::env::temp_dir().join("mulink_test_archive_damaged.mla");
+    let mut bytes = std::fs::read(path)?;
+    let entry = &reader.index().entries[1];
+    let (start, size) = (entry.offset.to_native() as usize, entry.size.to_native() as usize);
+    bytes[start..start + size].fill(0xff);
+    std::fs::write(&damaged, bytes)?;
+    let reader = ArchiveReader::<f32>::open(damaged.to_str().unwrap())?;
+    assert!(reader.chunk(1).is_err() && reader.chunk(1).is_err());
+    assert_eq!(reader.chunk(2)?.sample(0), sig[2000]);
+    assert_eq!(reader.chunk(2)?.sample(1), sig[2001]);
+
+    // A corrupt index is rejected on open, a chunk that disagrees with its entry on access
+    let corrupt = std::env::temp_dir().join("mulink_test_archive_corrupt.mla");
+    let corrupt = corrupt.to_str().unwrap();
+    let write_corrupt = |edit: &dyn Fn(&mut Vec<IndexEntry>)| -> anyhow::Result<()> {
+        let mut writer = ArchiveWriter::<f32>::create(corrupt, 48000.0)?;
+        for chunk in sig.split_chunks(1000).iter().take(3) {
+            writer.write(chunk)?;
+        }
+        edit(&mut writer.index.entries);
+        writer.finish()
+    };
+    write_corrupt(&|entries| entries.swap(0, 1))?;
+    assert!(ArchiveReader::<f32>::open(corrupt).is_err());
+    write_corrupt(&|entries| entries[0].len = 1500)?;
+    assert!(ArchiveReader::<f32>::open(corrupt).is_err());
+    write_corrupt(&|entries| entries[2].len = u64::MAX)?;
+    assert!(ArchiveReader::<f32>::open(corrupt).is_err());
+    write_corrupt(&|entries| entries[2].time = 5000)?;
+    let reader = ArchiveReader::<f32>::open(corrupt)?;
+    assert!(reader.chunk(2).is_err() && reader.read(0, 6000).is_err());
+    assert_eq!(reader.read(500, 1500)?.to_vec(), sig[500..1500].to_vec());
+    write_corrupt(&|entries| entries[1].len = 900)?;
+    let reader = ArchiveReader::<f32>::open(corrupt)?;
+    assert!(reader.chunk(1).is_err() && reader.chunk(2).is_ok());
+
+    // Whole-signal round trip
+    let path64 = std::env::temp_dir().join("mulink_test_archive64.mla");
+    let sig64 = Signal::from_function(8000.0, 777, |x| x);
+    sig64.write_archive(path64.to_str().unwrap())?;
+    let back = Signal::<f64>::read_archive(path64.to_str().unwrap())?;
+    assert_eq!((back.time, back.sample_rate, back.to_vec()), (0, 8000.0, sig64.to_vec()));
+    Ok(())
+}
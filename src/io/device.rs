@@ -0,0 +1,610 @@
+use std::{any::Any, path::{Path, PathBuf}, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, Data, FromSample, Sample, SampleFormat, SizedSample, StreamConfig, SupportedStreamConfigRange};
+use log::warn;
+use num::Complex;
+
+use crate::{core::{rt_ring::{rt_playback, rt_ring, RtConsumer, RtProducer}, signal::SignalMeta, stream::AudioStream}, prelude::*};
+
+// Capture and playback. An `AudioHost` opens device streams and calls back with interleaved
+// buffers in the device's own sample format; `Capture` and `Playback` convert them from/to
+// `Complex<T>` (channel 0 is I, channel 1 is Q) through the real-time rings of `rt_ring`.
+// `CpalHost` talks to sound cards, `NullHost` plays WAV files (or silence) for tests and CI.
+
+/// What to open, unset fields take the device's defaults
+#[derive(Clone, Debug, Default)]
+pub struct DeviceConfig {
+    /// Case-insensitive substring of the device name
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub format: Option<SampleFormat>,
+    /// Frames per callback
+    pub period: Option<u32>,
+    /// Ring capacity in samples, defaults to half a second
+    pub ring: Option<usize>,
+}
+impl DeviceConfig {
+    pub fn with_device(mut self, name: &str) -> Self {
+        self.device = Some(name.to_string());
+        self
+    }
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+    pub fn with_channels(mut self, channels: u16) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+    pub fn with_format(mut self, format: SampleFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+    pub fn with_period(mut self, period: u32) -> Self {
+        self.period = Some(period);
+        self
+    }
+    pub fn with_ring(mut self, ring: usize) -> Self {
+        self.ring = Some(ring);
+        self
+    }
+    fn ring_len(&self, format: &StreamFormat) -> usize {
+        self.ring.unwrap_or(format.sample_rate as usize / 2).max(1)
+    }
+}
+
+/// Negotiated device stream format
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamFormat {
+    pub device: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub format: SampleFormat,
+    pub period: Option<u32>,
+}
+
+/// Device sample type that `Samples`/`SamplesMut` can carry
+pub trait DeviceSample: SizedSample + Send + 'static {
+    fn samples(data: &[Self]) -> Samples<'_>;
+    fn samples_mut(data: &mut [Self]) -> SamplesMut<'_>;
+}
+
+macro_rules! device_samples {
+    ($($variant:ident($ty:ty)),*) => {
+        /// Interleaved input buffer in the device format
+        pub enum Samples<'a> {
+            $($variant(&'a [$ty])),*
+        }
+        /// Interleaved output buffer in the device format
+        pub enum SamplesMut<'a> {
+            $($variant(&'a mut [$ty])),*
+        }
+        $(impl DeviceSample for $ty {
+            fn samples(data: &[Self]) -> Samples<'_> {
+                Samples::$variant(data)
+            }
+            fn samples_mut(data: &mut [Self]) -> SamplesMut<'_> {
+                SamplesMut::$variant(data)
+            }
+        })*
+        impl Samples<'_> {
+            fn from_data(data: &Data) -> Option<Samples<'_>> {
+                match data.sample_format() {
+                    $(SampleFormat::$variant => data.as_slice::<$ty>().map(Samples::$variant),)*
+                    _ => None,
+                }
+            }
+            /// Push whole frames into `producer`, returns the number of frames written
+            fn push_to<T: SignalType>(&self, channels: usize, producer: &mut RtProducer<T>) -> usize {
+                match self {
+                    $(Samples::$variant(data) => producer.push_iter(data.chunks_exact(channels).map(frame_to_complex::<T, $ty>)),)*
+                }
+            }
+        }
+        impl SamplesMut<'_> {
+            fn from_data(data: &mut Data) -> Option<SamplesMut<'_>> {
+                match data.sample_format() {
+                    $(SampleFormat::$variant => data.as_slice_mut::<$ty>().map(SamplesMut::$variant),)*
+                    _ => None,
+                }
+            }
+            /// Fill every frame from `consumer`
+            fn fill_from<T: SignalType>(&mut self, channels: usize, consumer: &mut RtConsumer<T>) {
+                match self {
+                    $(SamplesMut::$variant(data) => data.chunks_exact_mut(channels).for_each(|frame| complex_to_frame(consumer.pop(), frame)),)*
+                }
+            }
+        }
+    };
+}
+device_samples!(I8(i8), I16(i16), I32(i32), I64(i64), U8(u8), U16(u16), U32(u32), U64(u64), F32(f32), F64(f64));
+
+pub(crate) fn frame_to_complex<T: SignalType, S: Sample>(frame: &[S]) -> Complex<T> where f64: FromSample<S> {
+    let channel = |idx: usize| frame.get(idx).map_or(T::zero(), |x| T::from_f64(x.to_sample::<f64>()).unwrap_or_default());
+    Complex::new(channel(0), channel(1))
+}
+pub(crate) fn complex_to_frame<T: SignalType, S: Sample + FromSample<f64>>(sample: Complex<T>, frame: &mut [S]) {
+    for (idx, out) in frame.iter_mut().enumerate() {
+        *out = match idx {
+            0 => S::from_sample(sample.re.to_f64().unwrap_or_default()),
+            1 => S::from_sample(sample.im.to_f64().unwrap_or_default()),
+            _ => S::EQUILIBRIUM,
+        };
+    }
+}
+
+pub type InputCallback = Box<dyn FnMut(Samples<'_>) + Send>;
+pub type OutputCallback = Box<dyn FnMut(SamplesMut<'_>) + Send>;
+/// Running device stream, stops when dropped. May have to stay on the thread that opened it.
+pub type DeviceHandle = Box<dyn Any>;
+
+/// Source of device streams
+pub trait AudioHost {
+    fn input_devices(&self) -> Result<Vec<String>>;
+    fn output_devices(&self) -> Result<Vec<String>>;
+    fn negotiate_input(&self, config: &DeviceConfig) -> Result<StreamFormat>;
+    fn negotiate_output(&self, config: &DeviceConfig) -> Result<StreamFormat>;
+    /// Start an input stream, `callback` runs on the device's real-time thread
+    fn open_input(&self, format: &StreamFormat, callback: InputCallback) -> Result<DeviceHandle>;
+    fn open_output(&self, format: &StreamFormat, callback: OutputCallback) -> Result<DeviceHandle>;
+}
+
+/// Sound cards through cpal
+pub struct CpalHost {
+    host: cpal::Host,
+}
+impl CpalHost {
+    pub fn new(host: cpal::Host) -> CpalHost {
+        CpalHost { host }
+    }
+    fn device(&self, input: bool, name: Option<&str>) -> Result<cpal::Device> {
+        let found = match name {
+            None if input => self.host.default_input_device(),
+            None => self.host.default_output_device(),
+            Some(name) => {
+                let name = name.to_lowercase();
+                let mut devices = if input { self.host.input_devices()?.collect::<Vec<_>>() } else { self.host.output_devices()?.collect() };
+                let idx = devices.iter().position(|x| x.name().is_ok_and(|x| x.to_lowercase().contains(&name)));
+                idx.map(|idx| devices.swap_remove(idx))
+            }
+        };
+        found.ok_or(anyhow!("mulink-dsp::device_not_found: {}", name.unwrap_or("default")))
+    }
+    fn negotiate(&self, input: bool, config: &DeviceConfig) -> Result<StreamFormat> {
+        let device = self.device(input, config.device.as_deref())?;
+        let (ranges, default) = if input {
+            (device.supported_input_configs()?.collect::<Vec<_>>(), device.default_input_config()?)
+        } else {
+            (device.supported_output_configs()?.collect::<Vec<_>>(), device.default_output_config()?)
+        };
+        let sample_rate = config.sample_rate.unwrap_or(default.sample_rate().0);
+        let fits = |range: &&SupportedStreamConfigRange| {
+            config.channels.is_none_or(|x| x == range.channels())
+                && config.format.is_none_or(|x| x == range.sample_format())
+                && (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&sample_rate)
+        };
+        // The default config if it fits, otherwise float, then the widest integer format
+        let preference = [default.sample_format(), SampleFormat::F32, SampleFormat::I32, SampleFormat::I16];
+        let range = ranges.iter().filter(fits).min_by_key(|range| {
+            (range.channels() != default.channels(), preference.iter().position(|x| *x == range.sample_format()).unwrap_or(preference.len()))
+        });
+        let range = range.ok_or(anyhow!("mulink-dsp::device_config_unsupported: {config:?}"))?;
+        Ok(StreamFormat {
+            device: device.name()?,
+            sample_rate,
+            channels: range.channels(),
+            format: range.sample_format(),
+            period: config.period,
+        })
+    }
+    fn stream_config(format: &StreamFormat) -> StreamConfig {
+        StreamConfig {
+            channels: format.channels,
+            sample_rate: cpal::SampleRate(format.sample_rate),
+            buffer_size: format.period.map_or(cpal::BufferSize::Default, cpal::BufferSize::Fixed),
+        }
+    }
+}
+impl Default for CpalHost {
+    fn default() -> Self {
+        CpalHost::new(cpal::default_host())
+    }
+}
+impl AudioHost for CpalHost {
+    fn input_devices(&self) -> Result<Vec<String>> {
+        Ok(self.host.input_devices()?.filter_map(|x| x.name().ok()).collect())
+    }
+    fn output_devices(&self) -> Result<Vec<String>> {
+        Ok(self.host.output_devices()?.filter_map(|x| x.name().ok()).collect())
+    }
+    fn negotiate_input(&self, config: &DeviceConfig) -> Result<StreamFormat> {
+        self.negotiate(true, config)
+    }
+    fn negotiate_output(&self, config: &DeviceConfig) -> Result<StreamFormat> {
+        self.negotiate(false, config)
+    }
+    fn open_input(&self, format: &StreamFormat, mut callback: InputCallback) -> Result<DeviceHandle> {
+        let device = self.device(true, Some(&format.device))?;
+        let stream = device.build_input_stream_raw(&Self::stream_config(format), format.format, move |data: &Data, _: &cpal::InputCallbackInfo| {
+            if let Some(samples) = Samples::from_data(data) {
+                callback(samples);
+            }
+        }, |err| warn!("Input stream error: {err}"), None)?;
+        stream.play()?;
+        Ok(Box::new(stream))
+    }
+    fn open_output(&self, format: &StreamFormat, mut callback: OutputCallback) -> Result<DeviceHandle> {
+        let device = self.device(false, Some(&format.device))?;
+        let stream = device.build_output_stream_raw(&Self::stream_config(format), format.format, move |data: &mut Data, _: &cpal::OutputCallbackInfo| {
+            if let Some(samples) = SamplesMut::from_data(data) {
+                callback(samples);
+            }
+        }, |err| warn!("Output stream error: {err}"), None)?;
+        stream.play()?;
+        Ok(Box::new(stream))
+    }
+}
+
+/// In-process host without a sound card. Input plays a WAV file in its own format (or endless
+/// silence), output consumes at the negotiated rate and optionally records to a WAV file.
+/// Callbacks run on plain threads, paced in real time unless `with_realtime(false)` (input only).
+#[derive(Clone, Debug)]
+pub struct NullHost {
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    realtime: bool,
+}
+const NULL_DEVICE: &str = "null";
+const NULL_PERIOD: u32 = 256;
+impl NullHost {
+    pub fn new() -> NullHost {
+        NullHost { input: None, output: None, realtime: true }
+    }
+    pub fn with_input_wav(mut self, path: impl AsRef<Path>) -> Self {
+        self.input = Some(path.as_ref().to_path_buf());
+        self
+    }
+    pub fn with_output_wav(mut self, path: impl AsRef<Path>) -> Self {
+        self.output = Some(path.as_ref().to_path_buf());
+        self
+    }
+    /// `false` delivers the input file as fast as the callback runs, which overruns small rings
+    pub fn with_realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+    fn check(config: &DeviceConfig, format: StreamFormat) -> Result<StreamFormat> {
+        let mismatch = config.device.as_ref().is_some_and(|x| !x.eq_ignore_ascii_case(NULL_DEVICE))
+            || config.sample_rate.is_some_and(|x| x != format.sample_rate)
+            || config.channels.is_some_and(|x| x != format.channels)
+            || config.format.is_some_and(|x| x != format.format);
+        if mismatch {
+            return Err(anyhow!("mulink-dsp::device_config_unsupported: {config:?}, null device offers {format:?}"));
+        }
+        Ok(format)
+    }
+    /// Interleaved periods paced at `rate` until `stop`
+    fn spawn<F: FnMut() -> Result<bool> + Send + 'static>(rate: u32, period: u32, realtime: bool, mut run: F) -> DeviceHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let start = Instant::now();
+                let mut periods = 0_u32;
+                while !stop.load(Ordering::Acquire) {
+                    if realtime {
+                        let due = start + Duration::from_secs_f64(periods as f64 * period as f64 / rate as f64);
+                        thread::sleep(due.saturating_duration_since(Instant::now()));
+                    }
+                    periods += 1;
+                    if !run()? {
+                        break;
+                    }
+                }
+                Ok(())
+            })
+        };
+        Box::new(NullStream { stop, thread: Some(thread) })
+    }
+}
+impl Default for NullHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stops and joins the null device thread when dropped
+struct NullStream {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+impl Drop for NullStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        match self.thread.take().map(|x| x.join()) {
+            Some(Ok(Err(err))) => warn!("Null device failed: {err}"),
+            Some(Err(_)) => warn!("Null device panicked"),
+            _ => {}
+        }
+    }
+}
+
+/// Whole input file in the format the null device delivers
+enum WavFrames {
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    F32(Vec<f32>),
+}
+impl WavFrames {
+    fn read(path: &Path) -> Result<(hound::WavSpec, WavFrames)> {
+        let mut reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let frames = match (spec.sample_format, spec.bits_per_sample) {
+            (hound::SampleFormat::Float, _) => WavFrames::F32(reader.samples::<f32>().collect::<Result<_, _>>()?),
+            (hound::SampleFormat::Int, bits) if bits <= 16 => WavFrames::I16(reader.samples::<i16>().map(|x| x.map(|x| x << (16 - bits))).collect::<Result<_, _>>()?),
+            // Scaled to full range, e.g. 24-bit files
+            (hound::SampleFormat::Int, bits) => WavFrames::I32(reader.samples::<i32>().map(|x| x.map(|x| x << (32 - bits))).collect::<Result<_, _>>()?),
+        };
+        Ok((spec, frames))
+    }
+    fn format(&self) -> SampleFormat {
+        match self {
+            WavFrames::I16(_) => SampleFormat::I16,
+            WavFrames::I32(_) => SampleFormat::I32,
+            WavFrames::F32(_) => SampleFormat::F32,
+        }
+    }
+}
+
+impl AudioHost for NullHost {
+    fn input_devices(&self) -> Result<Vec<String>> {
+        Ok(vec![NULL_DEVICE.to_string()])
+    }
+    fn output_devices(&self) -> Result<Vec<String>> {
+        Ok(vec![NULL_DEVICE.to_string()])
+    }
+    fn negotiate_input(&self, config: &DeviceConfig) -> Result<StreamFormat> {
+        let format = match &self.input {
+            Some(path) => {
+                let (spec, frames) = WavFrames::read(path)?;
+                StreamFormat { device: NULL_DEVICE.to_string(), sample_rate: spec.sample_rate, channels: spec.channels, format: frames.format(), period: config.period }
+            }
+            None => StreamFormat {
+                device: NULL_DEVICE.to_string(),
+                sample_rate: config.sample_rate.unwrap_or(48000),
+                channels: config.channels.unwrap_or(2),
+                format: SampleFormat::F32,
+                period: config.period,
+            },
+        };
+        Self::check(config, format)
+    }
+    fn negotiate_output(&self, config: &DeviceConfig) -> Result<StreamFormat> {
+        let format = config.format.unwrap_or(SampleFormat::F32);
+        if ![SampleFormat::I16, SampleFormat::I32, SampleFormat::F32].contains(&format) {
+            return Err(anyhow!("mulink-dsp::device_config_unsupported: null output device cannot write {format}"));
+        }
+        Self::check(config, StreamFormat {
+            device: NULL_DEVICE.to_string(),
+            sample_rate: config.sample_rate.unwrap_or(48000),
+            channels: config.channels.unwrap_or(2),
+            format,
+            period: config.period,
+        })
+    }
+    fn open_input(&self, format: &StreamFormat, mut callback: InputCallback) -> Result<DeviceHandle> {
+        let period = format.period.unwrap_or(NULL_PERIOD);
+        let len = period as usize * format.channels as usize;
+        let Some(path) = &self.input else {
+            let silence = vec![0.0_f32; len];
+            return Ok(Self::spawn(format.sample_rate, period, true, move || {
+                callback(f32::samples(&silence));
+                Ok(true)
+            }));
+        };
+        let (_, frames) = WavFrames::read(path)?;
+        let mut offset = 0;
+        Ok(Self::spawn(format.sample_rate, period, self.realtime, move || {
+            let end = offset + len;
+            let more = match &frames {
+                WavFrames::I16(x) => { callback(i16::samples(&x[offset..end.min(x.len())])); end < x.len() }
+                WavFrames::I32(x) => { callback(i32::samples(&x[offset..end.min(x.len())])); end < x.len() }
+                WavFrames::F32(x) => { callback(f32::samples(&x[offset..end.min(x.len())])); end < x.len() }
+            };
+            offset = end;
+            // End of file ends the stream, the callback (and the capture's producer) is dropped
+            Ok(more)
+        }))
+    }
+    fn open_output(&self, format: &StreamFormat, callback: OutputCallback) -> Result<DeviceHandle> {
+        let spec = |bits, sample_format| hound::WavSpec { channels: format.channels, sample_rate: format.sample_rate, bits_per_sample: bits, sample_format };
+        let period = format.period.unwrap_or(NULL_PERIOD);
+        match format.format {
+            SampleFormat::I16 => self.spawn_output::<i16>(format, period, spec(16, hound::SampleFormat::Int), callback),
+            SampleFormat::I32 => self.spawn_output::<i32>(format, period, spec(32, hound::SampleFormat::Int), callback),
+            _ => self.spawn_output::<f32>(format, period, spec(32, hound::SampleFormat::Float), callback),
+        }
+    }
+}
+impl NullHost {
+    /// The WAV file is finalized when the device is dropped
+    fn spawn_output<S: DeviceSample + hound::Sample>(&self, format: &StreamFormat, period: u32, spec: hound::WavSpec, mut callback: OutputCallback) -> Result<DeviceHandle> {
+        let mut writer = self.output.as_ref().map(|path| hound::WavWriter::create(path, spec)).transpose()?;
+        let mut buffer = vec![S::EQUILIBRIUM; period as usize * format.channels as usize];
+        Ok(Self::spawn(format.sample_rate, period, true, move || {
+            callback(S::samples_mut(&mut buffer));
+            if let Some(writer) = writer.as_mut() {
+                buffer.iter().try_for_each(|x| writer.write_sample(*x))?;
+            }
+            Ok(true)
+        }))
+    }
+}
+
+/// Input device feeding an `AudioStream`. Chunks carry the capture start as `meta.epoch`, lost
+/// samples (ring overruns) show up as gaps.
+pub struct Capture<T: SignalType> {
+    format: StreamFormat,
+    stream: Arc<AudioStream<T>>,
+    handle: Option<DeviceHandle>,
+    pump: Option<JoinHandle<Result<()>>>,
+}
+impl<T: SignalType> Capture<T> {
+    /// Open and start the input device, chunks of `chunk_len` samples go to `stream()`
+    pub fn open(host: &dyn AudioHost, config: &DeviceConfig, chunk_len: usize) -> Result<Capture<T>> {
+        let format = host.negotiate_input(config)?;
+        let stream = Arc::new(AudioStream::new());
+        let (mut producer, pump) = rt_ring::<T>(stream.clone(), format.sample_rate as f64, config.ring_len(&format), chunk_len);
+        let pump = pump.with_meta(SignalMeta { epoch: Some(Utc::now()), ..Default::default() }).spawn(Duration::from_millis(2));
+        let channels = format.channels.max(1) as usize;
+        let handle = host.open_input(&format, Box::new(move |samples: Samples| {
+            samples.push_to(channels, &mut producer);
+        }))?;
+        Ok(Capture { format, stream, handle: Some(handle), pump: Some(pump) })
+    }
+    pub fn format(&self) -> &StreamFormat {
+        &self.format
+    }
+    pub fn stream(&self) -> &Arc<AudioStream<T>> {
+        &self.stream
+    }
+    /// Stop the device, deliver what was captured and close the stream
+    pub fn stop(mut self) -> Result<()> {
+        self.handle.take();
+        self.join()
+    }
+    /// Wait for the device to end by itself (e.g. the null host's input file), then close the stream
+    pub fn wait(mut self) -> Result<()> {
+        self.join()
+    }
+    fn join(&mut self) -> Result<()> {
+        let result = self.pump.take().map(|x| x.join().map_err(|_| anyhow!("mulink-dsp::capture_pump_panicked"))?).unwrap_or(Ok(()));
+        self.handle.take();
+        result
+    }
+}
+impl<T: SignalType> Drop for Capture<T> {
+    fn drop(&mut self) {
+        self.handle.take();
+        let _ = self.join();
+    }
+}
+
+/// `AudioStream` played out of an output device. Chunks are not resampled, they should be at
+/// the negotiated rate. Silence is played while the stream has nothing to send.
+pub struct Playback {
+    format: StreamFormat,
+    handle: Option<DeviceHandle>,
+    feeder: Option<JoinHandle<Result<()>>>,
+    underruns: Arc<AtomicU64>,
+}
+impl Playback {
+    /// Subscribe to `stream` and start the output device
+    pub fn open<T: SignalType>(host: &dyn AudioHost, config: &DeviceConfig, stream: &AudioStream<T>) -> Result<Playback> {
+        let format = host.negotiate_output(config)?;
+        let (feeder, mut consumer) = rt_playback(stream, config.ring_len(&format));
+        let feeder = feeder.spawn(Duration::from_millis(2));
+        let underruns = Arc::new(AtomicU64::new(0));
+        let channels = format.channels.max(1) as usize;
+        let handle = {
+            let underruns = underruns.clone();
+            host.open_output(&format, Box::new(move |mut samples: SamplesMut| {
+                samples.fill_from(channels, &mut consumer);
+                underruns.store(consumer.underruns(), Ordering::Relaxed);
+            }))?
+        };
+        Ok(Playback { format, handle: Some(handle), feeder: Some(feeder), underruns })
+    }
+    pub fn format(&self) -> &StreamFormat {
+        &self.format
+    }
+    /// Silent samples played so far, including while waiting for the first chunk
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+    /// Wait until the stream is closed and everything was played, then stop the device
+    pub fn finish(mut self) -> Result<()> {
+        let result = self.feeder.take().map(|x| x.join().map_err(|_| anyhow!("mulink-dsp::playback_feeder_panicked"))?).unwrap_or(Ok(()));
+        self.handle.take();
+        result
+    }
+    /// Stop the device now
+    pub fn stop(mut self) {
+        self.handle.take();
+    }
+}
+impl Drop for Playback {
+    fn drop(&mut self) {
+        // Dropping the device drops the consumer, which ends the feeder
+        self.handle.take();
+        if let Some(feeder) = self.feeder.take() {
+            let _ = feeder.join();
+        }
+    }
+}
+
+#[test]
+fn test_device() -> anyhow::Result<()> {
+    use crate::core::stream::StreamMonitor;
+
+    let dir = std::env::temp_dir().join(format!("mulink_device_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let input = dir.join("input.wav");
+    let output = dir.join("output.wav");
+
+    // Stereo 16-bit "device" input, I and Q as two ramps
+    let spec = hound::WavSpec { channels: 2, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut writer = hound::WavWriter::create(&input, spec)?;
+    for idx in 0..2000_i16 {
+        writer.write_sample(idx * 8)?;
+        writer.write_sample(-idx * 8)?;
+    }
+    writer.finalize()?;
+
+    // Capture the whole file as fast as possible, the ring holds all of it
+    let host = NullHost::new().with_input_wav(&input).with_realtime(false);
+    assert!(host.negotiate_input(&DeviceConfig::default().with_sample_rate(48000)).is_err());
+    // Only the null device itself is accepted by name
+    assert!(host.negotiate_input(&DeviceConfig::default().with_device("NULL")).is_ok());
+    assert!(["", "n", "ul", "null2"].iter().all(|name| host.negotiate_input(&DeviceConfig::default().with_device(name)).is_err()));
+    let capture = Capture::<f64>::open(&host, &DeviceConfig::default().with_ring(4096), 500)?;
+    assert_eq!((capture.format().sample_rate, capture.format().channels, capture.format().format), (8000, 2, SampleFormat::I16));
+    let sub = capture.stream().get_subscriber();
+    let stream = capture.stream().clone();
+    capture.wait()?;
+    assert!(stream.is_closed());
+    let chunks = sub.iter().collect::<Vec<_>>();
+    let mut monitor = StreamMonitor::new();
+    assert!(chunks.iter().all(|x| monitor.check(x).is_none() && x.meta.as_ref().is_some_and(|x| x.epoch.is_some())));
+    let captured = Signal::concat_all(chunks.iter().map(|x| &**x))?;
+    assert_eq!((captured.time, captured.len(), captured.sample_rate), (0, 2000, 8000.0));
+    assert!(captured.iter().enumerate().all(|(idx, x)| *x == Complex::new(idx as f64 * 8.0 / 32768.0, -(idx as f64) * 8.0 / 32768.0)));
+
+    // A tiny ring overruns, whatever is kept stays at its own stream time
+    let capture = Capture::<f64>::open(&host, &DeviceConfig::default().with_ring(300), 100)?;
+    let sub = capture.stream().get_subscriber();
+    capture.wait()?;
+    let chunks = sub.iter().collect::<Vec<_>>();
+    assert!(chunks.iter().all(|x| x.iter().enumerate().all(|(idx, s)| (s.re * 32768.0 / 8.0) as i64 == x.time + idx as i64)));
+    assert!(chunks.last().is_some_and(|x| x.end_time() <= 2000));
+
+    // Playback to a 16-bit file, the output starts with silence until the first chunk arrives
+    let source = AudioStream::<f64>::new();
+    let host = NullHost::new().with_output_wav(&output);
+    let playback = Playback::open(&host, &DeviceConfig::default().with_sample_rate(8000).with_format(SampleFormat::I16).with_period(64), &source)?;
+    for chunk in captured.split_chunks(400) {
+        source.lock()?.send(chunk)?;
+    }
+    source.close()?;
+    playback.finish()?;
+    let played = hound::WavReader::open(&output)?.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+    let start = played.iter().position(|x| *x != 0).unwrap_or(played.len()) / 2 - 1;
+    let expected = (0..2000_i16).flat_map(|idx| [idx * 8, -idx * 8]).collect::<Vec<_>>();
+    assert_eq!(&played[2 * start..2 * start + expected.len()], &expected[..]);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
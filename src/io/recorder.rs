@@ -0,0 +1,334 @@
+use std::{fs::{self, File}, io::BufWriter, marker::PhantomData, path::{Path, PathBuf}, thread::{self, JoinHandle}};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{TimeDelta, Utc};
+use cpal::{FromSample, Sample, SampleFormat};
+use hound::WavWriter;
+use num::Complex;
+
+use crate::{core::{signal::SignalMeta, stream::{AudioStream, Discontinuity, StreamMonitor}}, io::{device::complex_to_frame, wav::{meta_sidecar_lines, meta_sidecar_path, read_meta_sidecar}}, prelude::*};
+
+/// Largest header hound writes (WAVE_FORMAT_EXTENSIBLE), used to turn a size limit into samples
+const WAV_HEADER: u64 = 68;
+
+/// Samples missing from a recording
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordedGap {
+    /// Samples in the file before the gap
+    pub offset: u64,
+    /// Stream samples lost at `offset`
+    pub samples: i64,
+}
+
+/// What a recorded WAV file does not hold itself, stored next to it as `<path>.meta`.
+/// The `SignalMeta` lines are those of `write_meta_sidecar`, so `read_wav_complex` still finds them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    /// Stream time of the first sample in the file
+    pub time: i64,
+    pub sample_rate: f64,
+    /// Samples in the file
+    pub samples: u64,
+    pub gaps: Vec<RecordedGap>,
+    pub meta: Option<SignalMeta>,
+}
+impl Recording {
+    /// Stream time of file sample `offset`
+    pub fn time_at(&self, offset: u64) -> i64 {
+        let lost = self.gaps.iter().take_while(|gap| gap.offset <= offset).map(|gap| gap.samples).sum::<i64>();
+        self.time + offset as i64 + lost
+    }
+    /// One past the stream time of the last sample
+    pub fn end_time(&self) -> i64 {
+        self.time + self.samples as i64 + self.gaps.iter().map(|gap| gap.samples).sum::<i64>()
+    }
+    pub fn write_sidecar(&self, path: &str) -> Result<()> {
+        let mut lines = self.meta.as_ref().map(meta_sidecar_lines).unwrap_or_default();
+        lines.push(format!("time={}", self.time));
+        lines.push(format!("sample_rate={}", self.sample_rate));
+        lines.push(format!("samples={}", self.samples));
+        lines.extend(self.gaps.iter().map(|gap| format!("gap={},{}", gap.offset, gap.samples)));
+        fs::write(meta_sidecar_path(path), lines.join("\n") + "\n")?;
+        Ok(())
+    }
+    /// `None` when there is no sidecar or it only holds `SignalMeta`
+    pub fn read_sidecar(path: &str) -> Result<Option<Recording>> {
+        let sidecar = meta_sidecar_path(path);
+        if !Path::new(&sidecar).exists() {
+            return Ok(None);
+        }
+        let (mut time, mut sample_rate, mut samples, mut gaps, mut has_meta) = (None, None, None, Vec::new(), false);
+        for line in fs::read_to_string(&sidecar)?.lines() {
+            let Some((key, value)) = line.split_once('=') else { continue };
+            let value = value.trim();
+            match key.trim() {
+                "time" => time = Some(value.parse().context("Invalid time in sidecar")?),
+                "sample_rate" => sample_rate = Some(value.parse().context("Invalid sample_rate in sidecar")?),
+                "samples" => samples = Some(value.parse().context("Invalid samples in sidecar")?),
+                "gap" => {
+                    let (offset, lost) = value.split_once(',').ok_or(anyhow!("Invalid gap in sidecar: {value}"))?;
+                    gaps.push(RecordedGap { offset: offset.trim().parse().context("Invalid gap in sidecar")?, samples: lost.trim().parse().context("Invalid gap in sidecar")? });
+                }
+                "unit" => has_meta = true,
+                _ => {}
+            }
+        }
+        let (Some(time), Some(sample_rate), Some(samples)) = (time, sample_rate, samples) else {
+            return Ok(None);
+        };
+        let meta = if has_meta { read_meta_sidecar(path)? } else { None };
+        Ok(Some(Recording { time, sample_rate, samples, gaps, meta }))
+    }
+}
+
+/// File being written
+struct Segment {
+    path: PathBuf,
+    writer: WavWriter<BufWriter<File>>,
+    info: Recording,
+}
+
+/// Writes a stream into consecutive WAV files named `<prefix>_<UTC start>.wav`, each with a
+/// `Recording` sidecar. Samples are written back to back; lost samples are listed as gaps
+/// instead of being filled in. A `Reset`, a jump back in time or a sample rate change starts a
+/// new file. The start is wall-clock time from `meta.epoch` when chunks carry one, otherwise now.
+pub struct WavRecorder<T: SignalType> {
+    dir: PathBuf,
+    prefix: String,
+    format: SampleFormat,
+    channels: u16,
+    max_duration: Option<f64>,
+    max_size: Option<u64>,
+    segment: Option<Segment>,
+    monitor: StreamMonitor,
+    files: Vec<PathBuf>,
+    _sample: PhantomData<T>,
+}
+impl<T: SignalType> WavRecorder<T> {
+    /// 32-bit float I/Q files in `dir`, which is created when needed. No rotation.
+    pub fn new(dir: impl AsRef<Path>) -> WavRecorder<T> {
+        WavRecorder {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: "rec".to_string(),
+            format: SampleFormat::F32,
+            channels: 2,
+            max_duration: None,
+            max_size: None,
+            segment: None,
+            monitor: StreamMonitor::new(),
+            files: Vec::new(),
+            _sample: PhantomData,
+        }
+    }
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+    /// `I16`, `I32` or `F32`, anything else is written as `F32`. Integers are full scale at 1.0.
+    pub fn with_format(mut self, format: SampleFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// Channel 0 is I, 1 is Q (as for devices), further channels are silent
+    pub fn with_channels(mut self, channels: u16) -> Self {
+        self.channels = channels.max(1);
+        self
+    }
+    /// Start a new file after `seconds` of samples
+    pub fn with_max_duration(mut self, seconds: f64) -> Self {
+        self.max_duration = Some(seconds);
+        self
+    }
+    /// Start a new file before one grows beyond `bytes`
+    pub fn with_max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+    fn bits(&self) -> u16 {
+        match self.format {
+            SampleFormat::I16 => 16,
+            _ => 32,
+        }
+    }
+    /// Samples per file
+    fn limit(&self, sample_rate: f64) -> u64 {
+        let by_duration = self.max_duration.map(|seconds| (seconds * sample_rate).round() as u64);
+        let frame = self.channels as u64 * self.bits() as u64 / 8;
+        let by_size = self.max_size.map(|bytes| bytes.saturating_sub(WAV_HEADER) / frame);
+        by_duration.into_iter().chain(by_size).min().unwrap_or(u64::MAX).max(1)
+    }
+    /// Files completed so far
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+    pub fn write(&mut self, chunk: &Signal<T>) -> Result<()> {
+        let reset = self.monitor.check(chunk) == Some(Discontinuity::Reset);
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        match self.segment.as_mut() {
+            Some(segment) if reset || chunk.sample_rate != segment.info.sample_rate || chunk.time < segment.info.end_time() => self.rotate()?,
+            Some(segment) if chunk.time > segment.info.end_time() => {
+                let samples = chunk.time - segment.info.end_time();
+                segment.info.gaps.push(RecordedGap { offset: segment.info.samples, samples });
+            }
+            _ => {}
+        }
+        let limit = self.limit(chunk.sample_rate);
+        let mut offset = 0;
+        while offset < chunk.len() {
+            let mut segment = match self.segment.take() {
+                Some(segment) => segment,
+                None => self.open(chunk, offset)?,
+            };
+            let n = usize::min(chunk.len() - offset, usize::try_from(limit - segment.info.samples).unwrap_or(usize::MAX));
+            let samples = &chunk[offset..offset + n];
+            match self.format {
+                SampleFormat::I16 => write_frames::<T, i16>(&mut segment.writer, self.channels, samples)?,
+                SampleFormat::I32 => write_frames::<T, i32>(&mut segment.writer, self.channels, samples)?,
+                _ => write_frames::<T, f32>(&mut segment.writer, self.channels, samples)?,
+            }
+            segment.info.samples += n as u64;
+            offset += n;
+            if segment.info.samples >= limit {
+                self.close(segment)?;
+            } else {
+                self.segment = Some(segment);
+            }
+        }
+        Ok(())
+    }
+    /// New file for `chunk` from sample `offset` on
+    fn open(&self, chunk: &Signal<T>, offset: usize) -> Result<Segment> {
+        let time = chunk.time + offset as i64;
+        let start = chunk.meta.as_ref().and_then(|meta| meta.epoch).map_or_else(Utc::now, |epoch| {
+            epoch + TimeDelta::nanoseconds((time as f64 / chunk.sample_rate * 1e9).round() as i64)
+        });
+        fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let name = format!("{}_{}", self.prefix, start.format("%Y%m%dT%H%M%S%.3fZ"));
+        // Two files can start within the same millisecond, e.g. after a reset
+        let path = (0..).map(|n| match n {
+            0 => self.dir.join(format!("{name}.wav")),
+            n => self.dir.join(format!("{name}_{n}.wav")),
+        }).find(|path| !path.exists()).unwrap_or_default();
+        let spec = hound::WavSpec {
+            channels: self.channels,
+            sample_rate: chunk.sample_rate as u32,
+            bits_per_sample: self.bits(),
+            sample_format: match self.format {
+                SampleFormat::I16 | SampleFormat::I32 => hound::SampleFormat::Int,
+                _ => hound::SampleFormat::Float,
+            },
+        };
+        let writer = WavWriter::create(&path, spec).with_context(|| format!("Failed to create {}", path.display()))?;
+        trace!("Recording to {}", path.display());
+        let info = Recording { time, sample_rate: chunk.sample_rate, samples: 0, gaps: Vec::new(), meta: chunk.meta.clone() };
+        Ok(Segment { path, writer, info })
+    }
+    fn close(&mut self, segment: Segment) -> Result<()> {
+        segment.writer.finalize()?;
+        segment.info.write_sidecar(&segment.path.to_string_lossy())?;
+        self.files.push(segment.path);
+        Ok(())
+    }
+    /// Complete the current file, the next chunk starts a new one
+    pub fn rotate(&mut self) -> Result<()> {
+        match self.segment.take() {
+            Some(segment) => self.close(segment),
+            None => Ok(()),
+        }
+    }
+    /// Complete the current file and return every file written
+    pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.rotate()?;
+        Ok(std::mem::take(&mut self.files))
+    }
+    /// Record `stream` on a new thread until it is closed. Subscribes immediately.
+    pub fn spawn(mut self, stream: &AudioStream<T>) -> JoinHandle<Result<Vec<PathBuf>>> {
+        let sub = stream.get_subscriber();
+        thread::spawn(move || {
+            for chunk in sub.iter() {
+                self.write(&chunk)?;
+            }
+            self.finish()
+        })
+    }
+}
+impl<T: SignalType> Drop for WavRecorder<T> {
+    fn drop(&mut self) {
+        // Leave a readable file behind when recording stops on an error
+        let _ = self.rotate();
+    }
+}
+
+fn write_frames<T: SignalType, S: Sample + FromSample<f64> + hound::Sample>(writer: &mut WavWriter<BufWriter<File>>, channels: u16, samples: &[Complex<T>]) -> Result<()> {
+    let mut frame = vec![S::EQUILIBRIUM; channels as usize];
+    for sample in samples {
+        complex_to_frame(*sample, &mut frame);
+        frame.iter().try_for_each(|x| writer.write_sample(*x))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_recorder() -> anyhow::Result<()> {
+    use chrono::DateTime;
+
+    let dir = std::env::temp_dir().join("mulink_test_recorder");
+    let _ = fs::remove_dir_all(&dir);
+    let epoch = DateTime::parse_from_rfc3339("2025-05-19T12:00:00Z")?.with_timezone(&Utc);
+    let meta = SignalMeta { epoch: Some(epoch), center_frequency: Some(25000.0), ..Default::default() };
+    let chunk = |start: i64, len: usize| Signal::from_vec(1000.0, (start..start + len as i64).map(|t| Complex::new(t as f32 / 10000.0, 0.0)).collect()).with_meta(meta.clone());
+
+    // One second per file, 16 bit, mono. 50 samples lost after 900, then a restart.
+    let stream = AudioStream::<f32>::new();
+    let recorder = WavRecorder::new(&dir).with_format(SampleFormat::I16).with_channels(1).with_max_duration(1.0).spawn(&stream);
+    for start in [0, 300, 600, 950, 1250, 1550, 1850, 2150] {
+        if start == 950 {
+            stream.lock()?.overrun(50);
+        }
+        stream.lock()?.send(chunk(start, 300))?;
+    }
+    let restarted = SignalMeta { epoch: Some(epoch + TimeDelta::hours(1)), ..meta.clone() };
+    stream.lock()?.send_at(chunk(0, 300).with_meta(restarted.clone()), 0)?;
+    stream.close()?;
+    let files = recorder.join().unwrap()?;
+
+    let names = files.iter().map(|x| x.file_name().unwrap().to_string_lossy().to_string()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["rec_20250519T120000.000Z.wav", "rec_20250519T120001.050Z.wav", "rec_20250519T120002.050Z.wav", "rec_20250519T130000.000Z.wav"]);
+    let recordings = files.iter().map(|x| Recording::read_sidecar(&x.to_string_lossy())).collect::<Result<Vec<_>>>()?;
+    let recordings = recordings.into_iter().flatten().collect::<Vec<_>>();
+    let spans = recordings.iter().map(|x| (x.time, x.samples, x.end_time())).collect::<Vec<_>>();
+    assert_eq!(spans, vec![(0, 1000, 1050), (1050, 1000, 2050), (2050, 400, 2450), (0, 300, 300)]);
+    assert_eq!(recordings[0].gaps, vec![RecordedGap { offset: 900, samples: 50 }]);
+    assert_eq!((recordings[0].time_at(899), recordings[0].time_at(900)), (899, 950));
+    assert_eq!(recordings[0].meta.as_ref(), Some(&meta));
+    assert_eq!(recordings[3].meta.as_ref(), Some(&restarted));
+    // The samples are the stream's, minus the gap
+    let mut reader = hound::WavReader::open(&files[0])?;
+    assert_eq!((reader.spec().channels, reader.spec().bits_per_sample, reader.len()), (1, 16, 1000));
+    let samples = reader.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+    assert!(samples.iter().enumerate().all(|(idx, x)| {
+        let time = recordings[0].time_at(idx as u64) as f64;
+        (*x as f64 - time / 10000.0 * 32768.0).abs() <= 1.0
+    }));
+
+    // Rotation by size, without an epoch, written directly
+    let dir = dir.join("size");
+    let mut recorder = WavRecorder::<f32>::new(&dir).with_prefix("iq").with_max_size(WAV_HEADER + 100 * 8);
+    let sig = Signal::from_vec(8000.0, (0..250).map(|x| Complex::new(x as f32, -x as f32)).collect());
+    for part in sig.split_chunks(64) {
+        recorder.write(&part)?;
+    }
+    assert_eq!(recorder.files().len(), 2);
+    let files = recorder.finish()?;
+    assert_eq!(files.len(), 3);
+    let mut samples = Vec::new();
+    for file in &files {
+        assert!(fs::metadata(file)?.len() <= WAV_HEADER + 100 * 8);
+        assert!(file.file_name().unwrap().to_string_lossy().starts_with("iq_"));
+        samples.extend(hound::WavReader::open(file)?.samples::<f32>().collect::<Result<Vec<_>, _>>()?);
+    }
+    assert_eq!(samples, sig.iter().flat_map(|x| [x.re, x.im]).collect::<Vec<_>>());
+    Ok(())
+}
@@ -1,10 +1,11 @@
-use std::{fs::File, io::BufReader};
+use std::{fs::{self, File}, io::BufReader, path::Path};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use hound::SampleFormat;
 use num::{cast::AsPrimitive, Complex, FromPrimitive};
 
-use crate::prelude::*;
+use crate::{core::{multi_signal::MultiSignal, real_signal::RealSignal, signal::{SignalMeta, Unit}}, prelude::*};
 
 
 
@@ -50,7 +51,7 @@ pub fn read_wav_complex<T: SignalType>(path: &str) -> Result<Signal<T>> {
                 }
                 _ => {
                     return Err(anyhow::format_err!(
-                        "Unsupported channels count: {}",
+                        "Unsupported channels count: {} (use read_wav_multichannel)",
                         spec.channels
                     ))
                 }
@@ -90,13 +91,15 @@ pub fn read_wav_complex<T: SignalType>(path: &str) -> Result<Signal<T>> {
                 }
                 _ => {
                     return Err(anyhow::format_err!(
-                        "Unsupported channels count: {}",
+                        "Unsupported channels count: {} (use read_wav_multichannel)",
                         spec.channels
                     ))
                 }
             },
         };
-    Ok(Signal::from_vec(spec.sample_rate as f64, signal))
+    let mut signal = Signal::from_vec(spec.sample_rate as f64, signal);
+    signal.meta = read_meta_sidecar(path)?;
+    Ok(signal)
 }
 
 pub fn write_wav_complex<T: SignalType>(
@@ -126,5 +129,174 @@ pub fn write_wav_complex<T: SignalType>(
         }),
     };
     result?;
+    sync_meta_sidecar(path, signal.meta.as_ref())
+}
+
+/// Mono files only, use `read_wav_complex` for I/Q (2 channel) recordings
+pub fn read_wav_real<T: SignalType>(path: &str) -> Result<RealSignal<T>> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let len = reader.len() as usize;
+    if spec.channels != 1 {
+        return Err(anyhow::format_err!(
+            "Unsupported channels count for real signal: {}",
+            spec.channels
+        ));
+    }
+
+    let signal: Vec<T> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().try_fold(
+            Vec::<T>::with_capacity(len),
+            |mut acc, sample| -> Result<Vec<T>> {
+                acc.push(T::from_f32(sample?).unwrap_or_default());
+                Ok(acc)
+            },
+        )?,
+        hound::SampleFormat::Int => reader.samples::<i32>().try_fold(
+            Vec::<T>::with_capacity(len),
+            |mut acc, sample| -> Result<Vec<T>> {
+                acc.push(T::from_i32(sample?).unwrap_or_default());
+                Ok(acc)
+            },
+        )?,
+    };
+    let mut signal = RealSignal::from_vec(spec.sample_rate as f64, signal);
+    signal.meta = read_meta_sidecar(path)?;
+    Ok(signal)
+}
+
+pub fn write_wav_real<T: SignalType>(
+    path: &str,
+    signal: &RealSignal<T>,
+    sample_format: SampleFormat,
+    normalize: bool,
+) -> Result<()> {
+    write_wav_interleaved(path, signal.sample_rate, 1, signal, sample_format, normalize)?;
+    sync_meta_sidecar(path, signal.meta.as_ref())
+}
+
+/// One real channel per WAV channel (imaginary parts are zero)
+pub fn read_wav_multichannel<T: SignalType>(path: &str) -> Result<MultiSignal<T>> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let len = reader.len() as usize;
+
+    let samples: Vec<Complex<T>> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().try_fold(
+            Vec::<Complex<T>>::with_capacity(len),
+            |mut acc, sample| -> Result<Vec<Complex<T>>> {
+                acc.push(Complex::from(T::from_f32(sample?).unwrap_or_default()));
+                Ok(acc)
+            },
+        )?,
+        hound::SampleFormat::Int => reader.samples::<i32>().try_fold(
+            Vec::<Complex<T>>::with_capacity(len),
+            |mut acc, sample| -> Result<Vec<Complex<T>>> {
+                acc.push(Complex::from(T::from_i32(sample?).unwrap_or_default()));
+                Ok(acc)
+            },
+        )?,
+    };
+    let mut signal = MultiSignal::from_interleaved(spec.sample_rate as f64, spec.channels as usize, &samples)?;
+    signal.meta = read_meta_sidecar(path)?;
+    Ok(signal)
+}
+
+/// Writes the real part of every channel, use `write_wav_complex` per channel for I/Q data
+pub fn write_wav_multichannel<T: SignalType>(
+    path: &str,
+    signal: &MultiSignal<T>,
+    sample_format: SampleFormat,
+    normalize: bool,
+) -> Result<()> {
+    let samples = signal.interleaved().iter().map(|x| x.re).collect::<Vec<T>>();
+    write_wav_interleaved(path, signal.sample_rate, signal.n_channels() as u16, &samples, sample_format, normalize)?;
+    sync_meta_sidecar(path, signal.meta.as_ref())
+}
+
+/// Real, frame-major samples, optionally normalized to full scale of the output format
+fn write_wav_interleaved<T: SignalType>(
+    path: &str,
+    sample_rate: f64,
+    channels: u16,
+    samples: &[T],
+    sample_format: SampleFormat,
+    normalize: bool,
+) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: 32,
+        sample_format,
+    };
+    let peak = samples.iter().fold(T::zero(), |a, b| a.max(b.abs()));
+    let full_scale = match sample_format {
+        SampleFormat::Float => T::one(),
+        SampleFormat::Int => T::from_i32(i32::MAX).unwrap_or_default(),
+    };
+    let gain = if normalize && !peak.is_zero() { full_scale / peak } else { T::one() };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    match sample_format {
+        SampleFormat::Float => samples.iter().try_for_each(|x| writer.write_sample::<f32>((*x * gain).as_()))?,
+        SampleFormat::Int => samples.iter().try_for_each(|x| writer.write_sample::<i32>((*x * gain).as_()))?,
+    };
+    writer.finalize()?;
     Ok(())
 }
+
+/// WAV has no room for `SignalMeta`, so it lives next to the file as `<path>.meta`
+pub fn meta_sidecar_path(path: &str) -> String {
+    format!("{path}.meta")
+}
+
+/// Write the sidecar for `meta`, or remove a stale one left by an earlier file at `path`
+pub fn sync_meta_sidecar(path: &str, meta: Option<&SignalMeta>) -> Result<()> {
+    match meta {
+        Some(meta) => write_meta_sidecar(path, meta),
+        None => match fs::remove_file(meta_sidecar_path(path)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// One `key=value` per line
+pub fn write_meta_sidecar(path: &str, meta: &SignalMeta) -> Result<()> {
+    fs::write(meta_sidecar_path(path), meta_sidecar_lines(meta).join("\n") + "\n")?;
+    Ok(())
+}
+
+/// `key=value` lines of `meta`, for sidecars that carry more than the metadata
+pub fn meta_sidecar_lines(meta: &SignalMeta) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(epoch) = meta.epoch {
+        lines.push(format!("epoch={}", epoch.to_rfc3339()));
+    }
+    if let Some(fc) = meta.center_frequency {
+        lines.push(format!("center_frequency={fc}"));
+    }
+    lines.push(format!("unit={}", meta.unit));
+    lines.push(format!("gain={}", meta.gain));
+    lines
+}
+
+/// `None` when no sidecar exists
+pub fn read_meta_sidecar(path: &str) -> Result<Option<SignalMeta>> {
+    let sidecar = meta_sidecar_path(path);
+    if !Path::new(&sidecar).exists() {
+        return Ok(None);
+    }
+    let mut meta = SignalMeta::default();
+    for line in fs::read_to_string(&sidecar)?.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        match key.trim() {
+            "epoch" => meta.epoch = Some(DateTime::parse_from_rfc3339(value.trim()).context("Invalid epoch in sidecar")?.with_timezone(&Utc)),
+            "center_frequency" => meta.center_frequency = Some(value.trim().parse().context("Invalid center_frequency in sidecar")?),
+            "unit" => meta.unit = value.trim().parse::<Unit>()?,
+            "gain" => meta.gain = value.trim().parse().context("Invalid gain in sidecar")?,
+            _ => {}
+        }
+    }
+    Ok(Some(meta))
+}
@@ -0,0 +1,232 @@
+use std::{collections::VecDeque, fs::{self, File}, io::BufReader, marker::PhantomData, path::{Path, PathBuf}, sync::Arc, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use anyhow::{anyhow, Context, Result};
+use cpal::{FromSample, Sample};
+use hound::WavReader;
+use num::Complex;
+
+use crate::{core::stream::AudioStream, io::{device::frame_to_complex, recorder::Recording, wav::read_meta_sidecar}, prelude::*};
+
+/// File being read
+struct OpenFile {
+    reader: WavReader<BufReader<File>>,
+    info: Recording,
+    read: u64,
+}
+impl OpenFile {
+    fn open(path: &Path, time: i64) -> Result<OpenFile> {
+        let reader = WavReader::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let name = path.to_string_lossy();
+        let info = match Recording::read_sidecar(&name)? {
+            Some(info) => info,
+            // Plain WAV files continue where the previous one ended
+            None => Recording { time, sample_rate: reader.spec().sample_rate as f64, samples: 0, gaps: Vec::new(), meta: read_meta_sidecar(&name)? },
+        };
+        // The file is authoritative for its length, e.g. when recording stopped before the sidecar was updated
+        let info = Recording { samples: reader.duration() as u64, ..info };
+        trace!("Playing {} from {}", path.display(), info.time);
+        Ok(OpenFile { reader, info, read: 0 })
+    }
+    /// Next `n` samples, channel 0 is I and channel 1 is Q. Integers are full scale at 1.0.
+    fn read<T: SignalType>(&mut self, n: usize) -> Result<Vec<Complex<T>>> {
+        let spec = self.reader.spec();
+        let channels = spec.channels.max(1) as usize;
+        let count = n * channels;
+        let samples = match (spec.sample_format, spec.bits_per_sample) {
+            (hound::SampleFormat::Float, _) => to_complex(self.reader.samples::<f32>().take(count).collect::<Result<Vec<_>, _>>()?, channels),
+            (hound::SampleFormat::Int, bits) if bits <= 16 => to_complex(self.reader.samples::<i16>().take(count).map(|x| x.map(|x| x << (16 - bits))).collect::<Result<Vec<_>, _>>()?, channels),
+            (hound::SampleFormat::Int, bits) => to_complex(self.reader.samples::<i32>().take(count).map(|x| x.map(|x| x << (32 - bits))).collect::<Result<Vec<_>, _>>()?, channels),
+        };
+        if samples.len() != n {
+            return Err(anyhow!("mulink-dsp::wav_source_truncated: expected {n} samples, got {}", samples.len()));
+        }
+        self.read += n as u64;
+        Ok(samples)
+    }
+}
+
+fn to_complex<T: SignalType, S: Sample>(samples: Vec<S>, channels: usize) -> Vec<Complex<T>> where f64: FromSample<S> {
+    samples.chunks_exact(channels).map(frame_to_complex).collect()
+}
+
+/// Replays a WAV file, or a directory of them in name order (as `WavRecorder` writes them), as
+/// `Signal` chunks of `chunk_len` samples. Times come from `Recording` sidecars, so gaps and
+/// restarts reappear where they happened; plain files follow on from the previous one.
+/// Chunks end early only where the recording is not contiguous.
+pub struct WavSource<T: SignalType> {
+    files: VecDeque<PathBuf>,
+    current: Option<OpenFile>,
+    chunk_len: usize,
+    realtime: bool,
+    /// Start of the next plain file
+    next_time: i64,
+    _sample: PhantomData<T>,
+}
+impl<T: SignalType> WavSource<T> {
+    /// `path` is a WAV file or a directory of `.wav` files
+    pub fn open(path: impl AsRef<Path>) -> Result<WavSource<T>> {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            let mut files = fs::read_dir(path)?.map(|entry| Ok(entry?.path())).collect::<Result<Vec<_>>>()?;
+            files.retain(|x| x.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav")));
+            files.sort();
+            files
+        } else if path.exists() {
+            vec![path.to_path_buf()]
+        } else {
+            return Err(anyhow!("mulink-dsp::wav_source_not_found: {}", path.display()));
+        };
+        Ok(WavSource { files: files.into(), current: None, chunk_len: 1024, realtime: false, next_time: 0, _sample: PhantomData })
+    }
+    pub fn with_chunk_len(mut self, chunk_len: usize) -> Self {
+        self.chunk_len = chunk_len.max(1);
+        self
+    }
+    /// Send chunks as fast as they would have arrived live (including gaps) instead of as fast as possible
+    pub fn with_realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+    /// Up to `chunk_len` contiguous samples, across file boundaries where the times line up
+    fn next_chunk(&mut self) -> Result<Option<Signal<T>>> {
+        let mut out: Option<Signal<T>> = None;
+        loop {
+            let file = match self.current.as_mut() {
+                Some(file) => file,
+                None => match self.files.pop_front() {
+                    Some(path) => self.current.insert(OpenFile::open(&path, self.next_time)?),
+                    None => break,
+                },
+            };
+            if file.read >= file.info.samples {
+                self.next_time = file.info.end_time();
+                self.current = None;
+                continue;
+            }
+            let time = file.info.time_at(file.read);
+            if out.as_ref().is_some_and(|out| out.end_time() != time || out.sample_rate != file.info.sample_rate) {
+                break;
+            }
+            let next_gap = file.info.gaps.iter().map(|gap| gap.offset).find(|offset| *offset > file.read).unwrap_or(file.info.samples);
+            let want = self.chunk_len - out.as_ref().map_or(0, |out| out.len());
+            let n = usize::min(want, usize::try_from(next_gap - file.read).unwrap_or(usize::MAX));
+            let mut part = Signal::from_vec(file.info.sample_rate, file.read(n)?);
+            part.time = time;
+            part.meta = file.info.meta.clone();
+            match out.as_mut() {
+                Some(out) => out.concat(&part)?,
+                None => out = Some(part),
+            }
+            if out.as_ref().is_some_and(|out| out.len() == self.chunk_len) {
+                break;
+            }
+        }
+        Ok(out)
+    }
+    /// Send everything to `stream` at its original times. The first chunk carries a `Reset`,
+    /// later jumps show up as `Gap` or `Reset` as when they were recorded. Does not close `stream`.
+    pub fn run(self, stream: &AudioStream<T>) -> Result<()> {
+        let realtime = self.realtime;
+        let start = Instant::now();
+        let mut played = 0.0;
+        let mut end: Option<i64> = None;
+        for chunk in self {
+            let chunk = chunk?;
+            if realtime {
+                // A live chunk is complete at its last sample, lost samples took time as well
+                let lost = end.map_or(0, |end| (chunk.time - end).max(0));
+                played += (chunk.len() as i64 + lost) as f64 / chunk.sample_rate;
+                let due = start + Duration::from_secs_f64(played);
+                thread::sleep(due.saturating_duration_since(Instant::now()));
+            }
+            let tx = stream.lock()?;
+            if end.is_none() {
+                tx.reset();
+            }
+            end = Some(chunk.end_time());
+            let time = chunk.time;
+            tx.send_at(chunk, time)?;
+        }
+        Ok(())
+    }
+    /// `run` on a new thread, closing `stream` at the end
+    pub fn spawn(self, stream: Arc<AudioStream<T>>) -> JoinHandle<Result<()>> {
+        thread::spawn(move || {
+            let result = self.run(&stream);
+            stream.close()?;
+            result
+        })
+    }
+}
+impl<T: SignalType> Iterator for WavSource<T> {
+    type Item = Result<Signal<T>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk().transpose()
+    }
+}
+
+#[test]
+fn test_wav_source() -> anyhow::Result<()> {
+    use chrono::{DateTime, TimeDelta, Utc};
+    use crate::{core::{signal::SignalMeta, stream::{Discontinuity, StreamMonitor}}, io::recorder::WavRecorder};
+
+    let dir = std::env::temp_dir().join("mulink_test_wav_source");
+    let _ = fs::remove_dir_all(&dir);
+    let epoch = DateTime::parse_from_rfc3339("2025-05-19T12:00:00Z")?.with_timezone(&Utc);
+    let meta = SignalMeta { epoch: Some(epoch), ..Default::default() };
+    let chunk = |start: i64, len: usize| Signal::from_vec(1000.0, (start..start + len as i64).map(|t| Complex::new(t as f32, -t as f32)).collect()).with_meta(meta.clone());
+
+    // Recorded with a gap, rotation every 700 samples and a restart at 0
+    let live = AudioStream::<f32>::new();
+    let recorder = WavRecorder::new(&dir).with_max_duration(0.7).spawn(&live);
+    for start in [0, 300, 600, 950, 1250] {
+        if start == 950 {
+            live.lock()?.overrun(50);
+        }
+        live.lock()?.send(chunk(start, 300))?;
+    }
+    live.lock()?.send_at(chunk(0, 100).with_meta(SignalMeta { epoch: Some(epoch + TimeDelta::hours(1)), ..Default::default() }), 0)?;
+    live.close()?;
+    assert_eq!(recorder.join().unwrap()?.len(), 4);
+
+    // Replayed as fast as possible in 256 sample chunks
+    let replay = Arc::new(AudioStream::<f32>::new());
+    let sub = replay.get_subscriber();
+    WavSource::open(&dir)?.with_chunk_len(256).spawn(replay.clone()).join().unwrap()?;
+    let mut monitor = StreamMonitor::new();
+    let chunks = sub.iter().map(|x| (x.time, x.len(), monitor.check(&x), x)).collect::<Vec<_>>();
+    let spans = chunks.iter().map(|(time, len, discontinuity, _)| (*time, *len, *discontinuity)).collect::<Vec<_>>();
+    assert_eq!(spans, vec![
+        (0, 256, Some(Discontinuity::Reset)),
+        (256, 256, None),
+        (512, 256, None),
+        (768, 132, None),
+        (950, 256, Some(Discontinuity::Gap { samples: 50 })),
+        (1206, 256, None),
+        (1462, 88, None),
+        (0, 100, Some(Discontinuity::Reset)),
+    ]);
+    // Every sample is back at its original time, with the recording's metadata
+    assert!(chunks.iter().all(|(_, _, _, x)| x.iter().enumerate().all(|(idx, s)| s.re == (x.time + idx as i64) as f32 && s.im == -s.re)));
+    assert_eq!(chunks[0].3.meta, Some(meta.clone()));
+    assert_eq!(chunks[7].3.start_time(), Some(epoch + TimeDelta::hours(1)));
+
+    // A plain WAV file starts at 0, real time pacing takes the file's duration
+    let path = dir.join("plain").with_extension("wav");
+    let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut writer = hound::WavWriter::create(&path, spec)?;
+    (0..200).try_for_each(|x| writer.write_sample(x as i16 * 100))?;
+    writer.finalize()?;
+    let source = WavSource::<f64>::open(&path)?.with_chunk_len(50).with_realtime(true);
+    let replay = AudioStream::<f64>::new();
+    let sub = replay.get_subscriber();
+    let start = Instant::now();
+    source.run(&replay)?;
+    assert!(start.elapsed() >= Duration::from_millis(190));
+    replay.close()?;
+    let samples = sub.iter().flat_map(|x| x.to_vec()).collect::<Vec<_>>();
+    assert_eq!(samples.len(), 200);
+    assert!(samples.iter().enumerate().all(|(idx, x)| (x.re - idx as f64 * 100.0 / 32768.0).abs() < 1e-9 && x.im == 0.0));
+    assert!(WavSource::<f32>::open(dir.join("missing.wav")).is_err());
+    Ok(())
+}
@@ -2,12 +2,27 @@ pub mod prelude;
 pub mod logging;
 pub mod core {
     pub mod signal;
+    pub mod real_signal;
+    pub mod multi_signal;
     pub mod signal_ops;
+    pub mod signal_slice;
+    pub mod stats;
+    pub mod units;
+    pub mod fixed;
     pub mod stream;
+    pub mod rt_ring;
+    pub mod schedule;
+    pub mod graph;
+    pub mod tag;
     pub mod block {
         pub mod fft;
         pub mod refragment;
         pub mod filter;
+        pub mod resample;
+        pub mod farrow;
+        pub mod mixer;
+        pub mod hilbert;
+        pub mod fixed_filter;
     }
     pub mod gen {
         pub mod fir;
@@ -20,6 +35,10 @@ pub mod core {
 }
 pub mod io {
     pub mod wav;
+    pub mod archive;
+    pub mod device;
+    pub mod recorder;
+    pub mod wav_source;
 }
 pub mod plot {
     pub mod time;
@@ -3,10 +3,10 @@ pub use log::{debug, error, info, trace, warn};
 
 pub fn init_logging() {
     let env = Env::default().filter_or("MULINK_LOG_LVL", "info");
-    env_logger::init_from_env(env);
+    let _ = env_logger::try_init_from_env(env);
 }
 
 pub fn init_tracing() {
     let env = Env::default().filter_or("MULINK_LOG_LVL", "trace");
-    env_logger::init_from_env(env);
+    let _ = env_logger::try_init_from_env(env);
 }
\ No newline at end of file
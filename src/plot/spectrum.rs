@@ -38,12 +38,13 @@ fn color_from_intensity_db<T: AsPrimitive<f64>>(intensity: T, reference: f64) ->
 
 pub fn spectrogram<T: SignalType>(
     filename: &str,
-    signal: Signal<T>,
+    signal: impl Into<Signal<T>>,
     window: usize,
     noverlap: usize,
     log: bool,
     reference: Option<f64>,
 ) {
+    let signal: Signal<T> = signal.into();
     let reference = reference.unwrap_or(get_global_reference());
 
     //let signal = sig.samples.complex();
@@ -172,6 +173,10 @@ pub fn spectrogram<T: SignalType>(
         draw_vtick(y_offset, &text);
     };
 
+    if let Some(meta) = &signal.meta {
+        root.draw_text(&meta.describe(), &style, (5, 5)).unwrap();
+    }
+
     let n_divisions = window / 100 + 1;
     draw_hz_tick(0);
     for idx in 0..=n_divisions {
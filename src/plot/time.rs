@@ -1,4 +1,6 @@
 use num::Complex;
+
+use crate::prelude::{Signal, SignalType};
 use plotters::{chart::ChartBuilder, prelude::{BitMapBackend, DiscreteRanged, IntoDrawingArea, IntoLinspace, PathElement}, series::LineSeries, style::{RED, WHITE}};
 
 
@@ -129,4 +131,8 @@ pub fn plot_complex<T: Into<f64> + Clone>(filename: &str, label: &str, values: &
 
     root_area.present().expect("Unable to write result to file, please make sure 'plotters-doc-data' dir exists under current dir");
     //println!("processing X3 for plot");
-}
\ No newline at end of file
+}
+/// `plot_complex` with the signal metadata appended to the title
+pub fn plot_signal<T: SignalType + Into<f64>>(filename: &str, label: &str, signal: &Signal<T>) {
+    plot_complex(filename, &signal.plot_label(label), signal);
+}
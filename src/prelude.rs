@@ -1,3 +1,3 @@
 pub use log::trace;
 
-pub use crate::{core::signal::{FromVec, Signal, SignalType}, logging::init_tracing};
\ No newline at end of file
+pub use crate::{core::{multi_signal::MultiSignal, real_signal::RealSignal, signal::{FromVec, Signal, SignalType}}, logging::init_tracing};
\ No newline at end of file